@@ -1,64 +1,322 @@
 use crate::cli::CLI;
+use crate::clipboard::Osc52Clipboard;
+use crate::commands::{CommandPalette, CommandRegistry};
+use crate::completion::CompletionMenu;
+use crate::diff::DiffView;
+use crate::file_picker::FilePicker;
+use crate::grep::grep;
+use crate::help::help_text;
+use crate::lsp::{hover_text, locations, path_to_uri, LspClient, LspEvent};
 use crate::painter::Painter;
+use crate::session::Session;
+use crate::which_key::{parse_keys, WhichKey};
 
 use wind_view::boundaries::Boundaries;
+use wind_view::buffers::{BufferList, BufferSwitch};
+use wind_view::config::CursorShape;
+use wind_view::diagnostics::{Diagnostic, DiagnosticSeverity};
 use wind_view::document::Row;
-use wind_view::editor::{Editor, EditorMode, EditorStatus};
+use wind_view::editor::{Editor, EditorMode, EditorStatus, Motion, Operator};
+use wind_view::filetype::FileType;
+use wind_view::git::GitDiff;
+use wind_view::position::Position;
+use wind_view::quickfix::QuickfixEntry;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
+use crossterm::cursor::SetCursorStyle;
 use crossterm::event::*;
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 
-use futures_util::StreamExt;
+use futures_util::{FutureExt, StreamExt};
+
+use serde_json::{json, Value};
 
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::Rect;
 use ratatui::Terminal;
 
-use std::io::{stdout, Stdout};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::{interval, sleep_until};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, stdout, IsTerminal, Stdout};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const MIN_WIDTH: u16 = 80;
+const MIN_HEIGHT: u16 = 10;
+const PAINT_ATTEMPTS: usize = 3;
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(300);
+const SCROLL_ACCELERATION_AFTER: usize = 10;
+const SCROLL_ACCELERATION_STEP: usize = 5;
+const FILE_PICKER_DEBOUNCE: Duration = Duration::from_millis(50);
 
 pub struct App {
     terminal: Terminal<CrosstermBackend<Stdout>>,
-    editor: Editor,
+    buffers: BufferList,
     painter: Painter,
+    cursor_shape: Option<CursorShape>,
+    count: Option<usize>,
+    pending: String,
+    macros: HashMap<char, Vec<KeyEvent>>,
+    recording_macro: Option<(char, Vec<KeyEvent>)>,
+    last_macro: Option<char>,
+    replaying_macro: bool,
+    completion: Option<CompletionMenu>,
+    file_picker: Option<FilePicker>,
+    file_picker_deadline: Option<Instant>,
+    drag_anchor: Option<Position>,
+    /// When and where the last left click landed, and how many quick
+    /// clicks on that spot it ended.
+    last_click: Option<(Instant, (usize, usize), usize)>,
+    consecutive_move_key: Option<(KeyCode, u8)>,
+    clipboard: Option<Osc52Clipboard>,
+    commands: CommandRegistry,
+    command_palette: Option<CommandPalette>,
+    session: Option<Session>,
+    config_path: Option<PathBuf>,
+    readonly: bool,
+    which_key_pending: String,
+    which_key_deadline: Option<Instant>,
+    which_key_visible: bool,
+    grep_sender: UnboundedSender<Result<Vec<QuickfixEntry>>>,
+    grep_receiver: UnboundedReceiver<Result<Vec<QuickfixEntry>>>,
+    git_diff_sender: UnboundedSender<(PathBuf, Result<GitDiff>)>,
+    git_diff_receiver: UnboundedReceiver<(PathBuf, Result<GitDiff>)>,
+    lsp: Option<LspClient>,
+    lsp_document: Option<(PathBuf, u64)>,
+    lsp_sender: UnboundedSender<LspEvent>,
+    lsp_receiver: UnboundedReceiver<LspEvent>,
 }
 
 impl App {
     pub fn new(cli: CLI) -> Result<App> {
         let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
 
-        let painter = Painter::new(terminal.size()?);
+        let size = terminal.size()?;
+
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            bail!(
+                "Terminal too small: wind requires at least {}x{}",
+                MIN_WIDTH,
+                MIN_HEIGHT
+            );
+        }
+
+        let mut painter = Painter::new(Boundaries::new(size.width, size.height));
+
+        let mut files = cli.files;
+
+        if cli.diff {
+            let [_, other_file] = files.as_slice() else {
+                bail!("--diff compares exactly two files");
+            };
+
+            painter.diff = Some(DiffView::open(other_file.clone())?);
+
+            painter.recompute_areas(Boundaries::new(size.width, size.height));
+
+            files.truncate(1);
+        }
+
+        // Events come from the controlling terminal, so stdin is free to
+        // carry the document.
+        if files.is_empty() && !io::stdin().is_terminal() {
+            files.push(PathBuf::from("-"));
+        }
+
+        let mut files = files.into_iter();
+
+        let file_path = files.next();
+
+        let mut session = if cli.no_session {
+            None
+        } else {
+            Session::load()
+        };
+
+        let mut editor = Editor::new(file_path.clone(), cli.config.as_deref())?;
+
+        if let Some(session) = session.as_mut() {
+            editor.search_history = std::mem::take(&mut session.search_history).into();
+        }
+
+        // Only a bare `wind` offers to reopen the last files.
+        let session = session.filter(|session| file_path.is_none() && !session.files.is_empty());
+
+        editor.document_mut().readonly = cli.readonly;
+
+        editor.check_recovery();
+
+        if let Some(session) = session.as_ref() {
+            let names: Vec<String> = session
+                .files
+                .iter()
+                .map(|file| {
+                    file.path
+                        .file_name()
+                        .unwrap_or(file.path.as_os_str())
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+
+            editor.set_message(format!(
+                "Restore last session ({})? (y/n)",
+                names.join(", ")
+            ));
+        }
+
+        // Editors open at the position last left in their file.
+        match cli.line {
+            Some(line) => editor.goto_line(painter.get_text_boundaries(), line),
+            None => editor.scroll_into_view(painter.get_text_boundaries()),
+        }
+
+        let mut buffers = BufferList::new(editor);
+
+        for path in files {
+            let mut editor =
+                App::open_buffer(path, cli.config.as_deref(), cli.readonly, buffers.current())?;
+
+            editor.scroll_into_view(painter.get_text_boundaries());
+
+            buffers.push(editor);
+        }
+
+        let (grep_sender, grep_receiver) = unbounded_channel();
+
+        let (git_diff_sender, git_diff_receiver) = unbounded_channel();
+
+        let (lsp_sender, lsp_receiver) = unbounded_channel();
 
         Ok(App {
             terminal,
-            editor: Editor::new(cli.file_path)?,
+            buffers,
             painter,
+            cursor_shape: None,
+            count: None,
+            pending: String::new(),
+            macros: HashMap::new(),
+            recording_macro: None,
+            last_macro: None,
+            replaying_macro: false,
+            completion: None,
+            file_picker: None,
+            file_picker_deadline: None,
+            drag_anchor: None,
+            last_click: None,
+            consecutive_move_key: None,
+            clipboard: Osc52Clipboard::detect(),
+            commands: CommandRegistry::default(),
+            command_palette: None,
+            session,
+            config_path: cli.config,
+            readonly: cli.readonly,
+            which_key_pending: String::new(),
+            which_key_deadline: None,
+            which_key_visible: false,
+            grep_sender,
+            grep_receiver,
+            git_diff_sender,
+            git_diff_receiver,
+            lsp: None,
+            lsp_document: None,
+            lsp_sender,
+            lsp_receiver,
         })
     }
 
+    fn open_buffer(
+        path: PathBuf,
+        config_path: Option<&Path>,
+        readonly: bool,
+        first: &Editor,
+    ) -> Result<Editor> {
+        let mut editor = Editor::new(Some(path), config_path)?;
+
+        editor.document_mut().readonly = readonly;
+
+        editor.search_history = first.search_history.clone();
+
+        editor.check_recovery();
+
+        Ok(editor)
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         self.start_session()?;
 
-        self.main_loop().await?;
+        self.start_lsp();
 
-        self.end_session()?;
+        // A panic leaves the buffers in the recovery directory and the
+        // terminal usable before it carries on unwinding.
+        let result = match AssertUnwindSafe(self.main_loop()).catch_unwind().await {
+            Ok(result) => result,
 
-        Ok(())
+            Err(panic) => {
+                for editor in self.buffers.iter() {
+                    editor.write_recovery();
+                }
+
+                let _ = self.end_session();
+
+                panic::resume_unwind(panic);
+            }
+        };
+
+        if result.is_ok() {
+            for editor in self.buffers.iter() {
+                editor.remember_position();
+
+                editor.document().remove_swap();
+            }
+        }
+
+        // The error that ended the main loop matters most; one restoring
+        // the terminal is reported after it.
+        match (result, self.end_session()) {
+            (Ok(()), teardown) => teardown,
+            (Err(err), Ok(())) => Err(err),
+            (Err(err), Err(teardown)) => Err(anyhow!(
+                "{:#}\n\nRestoring the terminal also failed: {:#}",
+                err,
+                teardown
+            )),
+        }
     }
 
     fn start_session(&mut self) -> Result<()> {
         enable_raw_mode()?;
-        execute!(self.terminal.backend_mut(), EnterAlternateScreen)?;
+        execute!(
+            self.terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableBracketedPaste,
+            EnableMouseCapture
+        )?;
 
         Ok(())
     }
 
     fn end_session(&mut self) -> Result<()> {
-        execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        // A session that can't be written is not worth failing the exit for.
+        let _ = Session::from_buffers(&self.buffers).save();
+
+        execute!(
+            self.terminal.backend_mut(),
+            SetCursorStyle::DefaultUserShape,
+            DisableMouseCapture,
+            DisableBracketedPaste,
+            LeaveAlternateScreen
+        )?;
         disable_raw_mode()?;
 
         Ok(())
@@ -67,14 +325,132 @@ impl App {
     async fn main_loop(&mut self) -> Result<()> {
         let mut event_stream = EventStream::new();
 
+        let mut swap_interval = interval(SWAP_INTERVAL);
+
         loop {
-            self.painter.paint(&mut self.terminal, &self.editor)?;
+            self.paint()?;
+
+            self.update_cursor_shape()?;
+
+            let position = self.buffers.current().position;
+
+            let edit_row = position.row;
+
+            let message_deadline = self.buffers.current().message_deadline();
+
+            tokio::select! {
+                _ = sleep_until(message_deadline.unwrap_or_else(Instant::now).into()),
+                    if message_deadline.is_some() => self.buffers.current_mut().clear_message(),
+
+                _ = sleep_until(self.which_key_deadline.unwrap_or_else(Instant::now).into()),
+                    if self.which_key_deadline.is_some() =>
+                {
+                    self.which_key_deadline = None;
+
+                    self.which_key_visible = true;
+                }
+
+                _ = sleep_until(self.file_picker_deadline.unwrap_or_else(Instant::now).into()),
+                    if self.file_picker_deadline.is_some() =>
+                {
+                    self.file_picker_deadline = None;
+
+                    if let Some(file_picker) = self.file_picker.as_mut() {
+                        file_picker.refresh();
+                    }
+                }
+
+                _ = swap_interval.tick() => {
+                    for editor in self.buffers.iter_mut() {
+                        editor.write_swap();
+                    }
+                }
+
+                event = event_stream.next() => {
+                    if let Some(Ok(event)) = event {
+                        self.handle_terminal_event(event)?;
+                    }
+                }
+
+                Some(result) = self.grep_receiver.recv() => match result {
+                    Ok(entries) => self.buffers.current_mut().set_quickfix(entries),
+                    Err(err) => self.buffers.current_mut().set_message(err.to_string()),
+                },
+
+                Some(event) = self.lsp_receiver.recv() => self.handle_lsp_event(event),
+
+                Some((path, result)) = self.git_diff_receiver.recv() => {
+                    if self.buffers.current().document().canonical_path() == Some(path.as_path()) {
+                        self.buffers.current_mut().git_diff = result.unwrap_or_default();
+                    }
+                }
+            }
+
+            if self.buffers.current().document().edit_refused {
+                self.buffers
+                    .current_mut()
+                    .refuse_edit(self.painter.get_text_boundaries(), position);
+            }
+
+            if std::mem::take(&mut self.buffers.current_mut().pending_help) {
+                self.buffers
+                    .current_mut()
+                    .open_help(&help_text(&self.commands));
+            }
+
+            if let Some(pattern) = self.buffers.current_mut().pending_grep.take() {
+                let sender = self.grep_sender.clone();
+
+                tokio::spawn(async move {
+                    let _ = sender.send(grep(pattern).await);
+                });
+            }
+
+            if std::mem::take(&mut self.buffers.current_mut().pending_clipboard) {
+                if let Some(clipboard) = self.clipboard.as_ref() {
+                    // The terminal may not support OSC 52; nothing is lost.
+                    let _ = clipboard.copy(
+                        self.terminal.backend_mut(),
+                        &self.buffers.current().register,
+                    );
+                }
+            }
+
+            if let Some(switch) = self.buffers.current_mut().pending_buffer.take() {
+                self.switch_buffer(switch);
+            }
+
+            self.update_which_key();
+
+            if self.painter.update_split(self.buffers.current()) {
+                self.buffers
+                    .current_mut()
+                    .scroll_into_view(self.painter.get_text_boundaries());
+            }
+
+            let edit_row = edit_row.min(self.buffers.current().position.row);
+
+            self.buffers
+                .current_mut()
+                .sync_folds(self.painter.get_text_boundaries(), edit_row);
+
+            self.sync_lsp();
 
-            if let Some(Ok(event)) = event_stream.next().await {
-                self.handle_terminal_event(event)?;
+            if std::mem::take(&mut self.buffers.current_mut().pending_git_diff) {
+                if let Some(path) = self.buffers.current().document().canonical_path() {
+                    let path = path.to_path_buf();
+
+                    let sender = self.git_diff_sender.clone();
+
+                    tokio::task::spawn_blocking(move || {
+                        let result = GitDiff::compute(&path);
+
+                        let _ = sender.send((path, result));
+                    });
+                }
             }
 
-            if self.editor.status == EditorStatus::Exit {
+            if self.buffers.current().status == EditorStatus::Exit {
                 break;
             }
         }
@@ -82,138 +458,1614 @@ impl App {
         Ok(())
     }
 
-    fn handle_terminal_event(&mut self, event: Event) -> Result<()> {
-        match event {
-            Event::Resize(width, height) => {
-                self.terminal.resize(Rect::new(0, 0, width, height))?;
+    fn restore_session(&mut self, session: Session) {
+        let boundaries = self.painter.get_text_boundaries();
 
-                self.painter.recompute_areas(self.terminal.size()?);
-            }
+        let mut files = session.files.into_iter();
 
-            Event::Key(key_event) => self.handle_key_event(key_event)?,
+        let Some(first) = files.next() else {
+            return;
+        };
 
-            _ => (),
+        if self.buffers.current_mut().load_file(first.path, false) {
+            self.buffers
+                .current_mut()
+                .jump_to(boundaries, first.row, first.column);
         }
 
-        Ok(())
+        for file in files {
+            let opened = App::open_buffer(
+                file.path,
+                self.config_path.as_deref(),
+                self.readonly,
+                self.buffers.current(),
+            );
+
+            match opened {
+                Ok(mut editor) => {
+                    editor.jump_to(boundaries, file.row, file.column);
+
+                    self.buffers.push(editor);
+                }
+
+                Err(err) => self.buffers.current_mut().set_message(err.to_string()),
+            }
+        }
+
+        self.buffers.select(session.current);
+
+        self.buffers.current_mut().pending_git_diff = true;
     }
 
-    fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
-        let text_area = self.painter.get_text_area();
-        let text_area_boundaries = Boundaries::new(text_area.width, text_area.height);
+    fn update_which_key(&mut self) {
+        if self.pending == self.which_key_pending {
+            return;
+        }
 
-        match key_event.code {
-            KeyCode::Up => self.editor.move_up(text_area_boundaries, 1)?,
+        self.which_key_pending = self.pending.clone();
 
-            KeyCode::Down => self.editor.move_down(text_area_boundaries, 1)?,
+        if self.pending.is_empty() {
+            self.which_key_deadline = None;
 
-            KeyCode::Left => self.editor.move_left(text_area_boundaries, 1)?,
+            self.which_key_visible = false;
+        } else if !self.which_key_visible {
+            let delay = Duration::from_millis(self.buffers.current().config.which_key_delay);
 
-            KeyCode::Right => self.editor.move_right(text_area_boundaries, 1)?,
+            self.which_key_deadline = Some(Instant::now() + delay);
+        }
+    }
 
-            KeyCode::Home => self
-                .editor
-                .move_left(text_area_boundaries, self.editor.position.column)?,
+    fn paint(&mut self) -> Result<()> {
+        let mut attempts = 0;
+
+        if let Some(diff) = self.painter.diff.as_mut() {
+            diff.update(self.buffers.current().document());
+        }
+
+        self.buffers.current_mut().update_search();
 
-            KeyCode::End => {
-                let current_row_length = self.editor.document.row_len(self.editor.position.row);
+        self.painter.buffer_position =
+            (self.buffers.len() > 1).then(|| (self.buffers.index(), self.buffers.len()));
 
-                self.editor.move_right(
-                    text_area_boundaries,
-                    current_row_length.saturating_sub(self.editor.position.column),
-                )?;
+        let which_key = self
+            .which_key_visible
+            .then(|| {
+                WhichKey::for_mode(&self.buffers.current().mode)
+                    .lookup(&parse_keys(&self.pending))
+                    .map(WhichKey::continuations)
+            })
+            .flatten();
+
+        loop {
+            let Err(err) = self.painter.paint(
+                &mut self.terminal,
+                self.buffers.current(),
+                self.file_picker.as_ref(),
+                self.command_palette.as_ref(),
+                self.completion.as_ref(),
+                which_key.as_deref(),
+            ) else {
+                return Ok(());
+            };
+
+            let transient = err.downcast_ref::<io::Error>().is_some_and(|err| {
+                matches!(
+                    err.kind(),
+                    io::ErrorKind::Interrupted
+                        | io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                )
+            });
+
+            attempts += 1;
+
+            if !transient || attempts >= PAINT_ATTEMPTS {
+                return Err(err.context("Failed to draw the editor"));
             }
+        }
+    }
 
-            _ => (),
+    fn switch_buffer(&mut self, switch: BufferSwitch) {
+        if self.buffers.len() == 1 {
+            self.buffers
+                .current_mut()
+                .set_message("There is only one buffer".to_owned());
+
+            return;
+        }
+
+        self.buffers.switch(switch);
+
+        let editor = self.buffers.current_mut();
+
+        editor.pending_git_diff = true;
+
+        editor.scroll_into_view(self.painter.get_text_boundaries());
+    }
+
+    fn start_lsp(&mut self) {
+        let filetype = self.buffers.current().config.filetype.name();
+
+        let Some(command) = self
+            .buffers
+            .current()
+            .config
+            .lsp_servers
+            .get(filetype)
+            .cloned()
+        else {
+            return;
         };
 
-        match self.editor.mode {
-            EditorMode::Normal => match key_event.code {
-                KeyCode::Char('q') => {
-                    if key_event.modifiers.contains(KeyModifiers::CONTROL) {
-                        self.editor.status = EditorStatus::Exit;
-                    }
+        if self.buffers.current().document().path.is_none() {
+            return;
+        }
+
+        let root = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+
+        match LspClient::start(&command, filetype, &root, self.lsp_sender.clone()) {
+            Ok(client) => self.lsp = Some(client),
+            Err(err) => self
+                .buffers
+                .current_mut()
+                .messages
+                .push(format!("{:#}", err)),
+        }
+    }
+
+    fn sync_lsp(&mut self) {
+        let saved = std::mem::take(&mut self.buffers.current_mut().saved);
+
+        let Some(client) = self.lsp.as_mut().filter(|client| client.initialized) else {
+            return;
+        };
+
+        let document = self.buffers.current().document();
+
+        let Some(path) = document.path.clone() else {
+            return;
+        };
+
+        match self.lsp_document.as_mut() {
+            Some((open_path, revision)) if *open_path == path => {
+                if *revision != document.revision {
+                    client.did_change(&path, document.text());
+
+                    *revision = document.revision;
                 }
 
-                KeyCode::Char('i') => {
-                    self.editor.mode = EditorMode::Insert;
+                if saved {
+                    client.did_save(&path);
                 }
+            }
 
-                KeyCode::Char('a') => {
-                    let current_row_length = self.editor.document.row_len(self.editor.position.row);
+            _ => {
+                if let Some((open_path, _)) = self.lsp_document.take() {
+                    client.did_close(&open_path);
+                }
 
-                    if self.editor.position.column <= current_row_length.saturating_sub(1) {
-                        self.editor.move_right(text_area_boundaries, 1)?;
-                    }
+                if self.buffers.current().config.filetype.name() == client.language_id {
+                    client.did_open(&path, document.text());
 
-                    self.editor.mode = EditorMode::Insert;
+                    self.lsp_document = Some((path, document.revision));
                 }
+            }
+        }
+    }
 
-                KeyCode::Char('o') => {
-                    self.editor
-                        .document
-                        .rows
-                        .insert(self.editor.position.row.saturating_add(1), Row::default());
+    /// Feeds a recorded macro back through the key handler `count` times.
+    /// Failures in the editor surface as messages, so a new message stops
+    /// playback early the way a failed command does in Vim.
+    fn play_macro(&mut self, register: char, count: usize) -> Result<()> {
+        if self.replaying_macro {
+            return Ok(());
+        }
+
+        let register = match register {
+            '@' => match self.last_macro {
+                Some(register) => register,
+                None => return Ok(()),
+            },
+            register => register,
+        };
+
+        let Some(events) = self.macros.get(&register).cloned() else {
+            self.buffers
+                .current_mut()
+                .set_message(format!("Register {} is empty", register));
+
+            return Ok(());
+        };
+
+        self.last_macro = Some(register);
+
+        self.replaying_macro = true;
+
+        let result = self.replay_events(&events, count);
 
-                    self.editor.move_down(text_area_boundaries, 1)?;
+        self.replaying_macro = false;
 
-                    self.editor.mode = EditorMode::Insert;
+        result
+    }
+
+    fn replay_events(&mut self, events: &[KeyEvent], count: usize) -> Result<()> {
+        for _ in 0..count {
+            for key_event in events {
+                let message_time = self.buffers.current().message_time;
+
+                self.handle_key_event(*key_event)?;
+
+                if self.buffers.current().message_time != message_time
+                    || self.buffers.current().status == EditorStatus::Exit
+                {
+                    return Ok(());
                 }
+            }
+        }
 
-                KeyCode::Char('O') => {
-                    self.editor
-                        .document
-                        .rows
-                        .insert(self.editor.position.row, Row::default());
+        Ok(())
+    }
+
+    fn request_from_key(&mut self, request: fn(&mut LspClient, &Path, usize, usize) -> u64) {
+        if self.lsp.as_ref().is_some_and(|client| client.initialized) {
+            self.request_at_cursor(request);
+        } else {
+            self.buffers
+                .current_mut()
+                .set_message("No language server is running".to_owned());
+        }
+    }
+
+    fn request_completion(&mut self) {
+        self.request_at_cursor(LspClient::completion);
+    }
+
+    /// Sends a position-based request for the cursor, flushing pending edits
+    /// first so the server sees the text the position refers to.
+    fn request_at_cursor(&mut self, request: fn(&mut LspClient, &Path, usize, usize) -> u64) {
+        self.sync_lsp();
+
+        let Some(client) = self.lsp.as_mut().filter(|client| client.initialized) else {
+            return;
+        };
+
+        let document = self.buffers.current().document();
+
+        let Some(path) = document.path.clone() else {
+            return;
+        };
+
+        let position = self.buffers.current().position;
+
+        let character = document
+            .rows
+            .get(position.row)
+            .map(|row| row.utf16_offset_from_grapheme_col(position.column))
+            .unwrap_or(0);
+
+        request(client, &path, position.row, character);
+    }
 
-                    self.editor
-                        .move_left(text_area_boundaries, self.editor.position.column)?;
+    fn update_diagnostics(&mut self, params: &Value) {
+        let document = self.buffers.current().document();
 
-                    self.editor.mode = EditorMode::Insert;
+        let is_open = document
+            .canonical_path()
+            .is_some_and(|path| params["uri"].as_str() == Some(&path_to_uri(path)));
+
+        if !is_open {
+            return;
+        }
+
+        let diagnostics = params["diagnostics"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|diagnostic| {
+                let start = &diagnostic["range"]["start"];
+
+                let row = start["line"].as_u64()? as usize;
+
+                let character = start["character"].as_u64()? as usize;
+
+                let col = document
+                    .rows
+                    .get(row)
+                    .map_or(0, |r| r.grapheme_col_from_utf16_offset(character));
+
+                Some(Diagnostic {
+                    row,
+                    col,
+                    severity: DiagnosticSeverity::from_lsp(diagnostic["severity"].as_u64()),
+                    message: diagnostic["message"].as_str()?.to_owned(),
+                })
+            })
+            .collect();
+
+        self.buffers.current_mut().set_diagnostics(diagnostics);
+    }
+
+    fn definition_entries(&self, result: &Value) -> Vec<QuickfixEntry> {
+        let document = self.buffers.current().document();
+
+        let open_path = document
+            .path
+            .as_ref()
+            .and_then(|path| path.canonicalize().ok());
+
+        locations(result)
+            .into_iter()
+            .map(|(path, line, character)| {
+                let text = if path.canonicalize().ok() == open_path {
+                    document.rows.get(line).map(|row| row.content.clone())
+                } else {
+                    fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|text| text.lines().nth(line).map(str::to_owned))
                 }
+                .unwrap_or_default();
 
-                KeyCode::Char('s') => {
-                    if key_event.modifiers.contains(KeyModifiers::CONTROL) {
-                        self.editor.save();
-                    }
+                let col = Row::from(text.clone()).grapheme_col_from_utf16_offset(character);
+
+                QuickfixEntry {
+                    path,
+                    line,
+                    col,
+                    text,
                 }
+            })
+            .collect()
+    }
 
-                KeyCode::Char('k') => self.editor.move_up(text_area_boundaries, 1)?,
+    fn triggers_completion(&self, ch: char) -> bool {
+        let position = self.buffers.current().position;
+
+        let previous = position.column.checked_sub(2).and_then(|column| {
+            self.buffers
+                .current()
+                .document()
+                .rows
+                .get(position.row)?
+                .grapheme_at(column)
+        });
+
+        let filetype = self.buffers.current().config.filetype;
+
+        match ch {
+            '.' => matches!(
+                filetype,
+                FileType::Rust
+                    | FileType::Python
+                    | FileType::Go
+                    | FileType::C
+                    | FileType::Cpp
+                    | FileType::Java
+                    | FileType::JavaScript
+                    | FileType::TypeScript
+                    | FileType::Kotlin
+                    | FileType::Swift
+                    | FileType::Ruby
+                    | FileType::Lua
+            ),
+            ':' => previous == Some(":") && matches!(filetype, FileType::Rust | FileType::Cpp),
+            _ => false,
+        }
+    }
 
-                KeyCode::Char('j') => self.editor.move_down(text_area_boundaries, 1)?,
+    fn handle_completion_key(&mut self, key_event: KeyEvent, boundaries: Boundaries) -> bool {
+        let Some(completion) = self.completion.as_mut() else {
+            return false;
+        };
 
-                KeyCode::Char('h') => self.editor.move_left(text_area_boundaries, 1)?,
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
 
-                KeyCode::Char('l') => self.editor.move_right(text_area_boundaries, 1)?,
+        match key_event.code {
+            KeyCode::Down => completion.move_selection(1),
 
-                _ => (),
-            },
+            KeyCode::Up => completion.move_selection(-1),
 
-            EditorMode::Insert => match key_event.code {
-                KeyCode::Char(ch) => {
-                    self.editor.document.insert(self.editor.position, ch);
+            KeyCode::Char('n') if ctrl => completion.move_selection(1),
 
-                    self.editor.move_right(text_area_boundaries, 1)?;
-                }
+            KeyCode::Char('p') if ctrl => completion.move_selection(-1),
 
-                KeyCode::Enter => {
-                    self.editor.document.insert(self.editor.position, '\n');
+            KeyCode::Tab | KeyCode::Enter => {
+                if let Some(item) = completion.selected() {
+                    let text = item.insert_text.clone();
 
-                    self.editor.move_right(text_area_boundaries, 1)?;
+                    self.buffers.current_mut().complete(boundaries, &text);
                 }
 
-                KeyCode::Delete => self.editor.document.delete(self.editor.position),
+                self.completion = None;
+            }
+
+            KeyCode::Esc => self.completion = None,
 
-                KeyCode::Backspace => {
-                    if self.editor.position.row > 0 || self.editor.position.column > 0 {
-                        self.editor.move_left(text_area_boundaries, 1)?;
+            _ => {
+                self.completion = None;
+
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn handle_lsp_event(&mut self, event: LspEvent) {
+        let Some(client) = self.lsp.as_mut() else {
+            return;
+        };
+
+        match event {
+            LspEvent::Response { id, result } => {
+                let method = client.take_request(id);
+
+                match (method.as_deref(), result) {
+                    (Some("initialize"), Ok(_)) => {
+                        client.initialized = true;
+
+                        client.notify("initialized", json!({}));
+                    }
 
-                        self.editor.document.delete(self.editor.position);
+                    (Some("textDocument/hover"), Ok(result)) => {
+                        let text = hover_text(&result["contents"]);
+
+                        if text.trim().is_empty() {
+                            self.buffers
+                                .current_mut()
+                                .set_message("No hover information".to_owned());
+                        } else {
+                            self.buffers.current_mut().hover_popup = Some(text.trim().to_owned());
+                        }
+                    }
+
+                    (Some("textDocument/definition"), Ok(result)) => {
+                        let entries = self.definition_entries(&result);
+
+                        let text_area_boundaries = self.painter.get_text_boundaries();
+
+                        self.buffers
+                            .current_mut()
+                            .jump_to_definitions(text_area_boundaries, entries);
+                    }
+
+                    (Some("textDocument/completion"), Ok(result))
+                        if self.buffers.current().mode == EditorMode::Insert =>
+                    {
+                        self.completion = CompletionMenu::from_response(&result);
+                    }
+
+                    (_, Err(err)) => {
+                        self.buffers
+                            .current_mut()
+                            .set_message(format!("Language server error: {}", err));
+                    }
+
+                    _ => (),
+                }
+            }
+
+            LspEvent::Notification { method, params } => match method.as_str() {
+                "window/showMessage" => {
+                    if let Some(message) = params["message"].as_str() {
+                        self.buffers.current_mut().set_message(message.to_owned());
                     }
                 }
 
+                "textDocument/publishDiagnostics" => self.update_diagnostics(&params),
+
+                _ => (),
+            },
+
+            LspEvent::Exited => {
+                self.lsp = None;
+
+                self.buffers.current_mut().diagnostics.clear();
+
+                self.lsp_document = None;
+
+                self.buffers
+                    .current_mut()
+                    .set_message("Language server exited".to_owned());
+            }
+        }
+    }
+
+    fn update_cursor_shape(&mut self) -> Result<()> {
+        let cursor_shape = self.buffers.current().cursor_shape();
+
+        if self.cursor_shape == Some(cursor_shape) {
+            return Ok(());
+        }
+
+        let cursor_style = match cursor_shape {
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+        };
+
+        execute!(self.terminal.backend_mut(), cursor_style)?;
+
+        self.cursor_shape = Some(cursor_shape);
+
+        Ok(())
+    }
+
+    fn handle_terminal_event(&mut self, event: Event) -> Result<()> {
+        match event {
+            Event::Resize(width, height) => {
+                self.terminal.resize(Rect::new(0, 0, width, height))?;
+
+                self.painter.recompute_areas(Boundaries::new(width, height));
+            }
+
+            Event::Key(key_event) => {
+                let position = self.buffers.current().position;
+
+                if let Some((_, events)) = self.recording_macro.as_mut() {
+                    events.push(key_event);
+                }
+
+                self.handle_key_event(key_event)?;
+
+                if (position.row, position.column)
+                    != (
+                        self.buffers.current().position.row,
+                        self.buffers.current().position.column,
+                    )
+                {
+                    self.buffers.current_mut().hover_popup = None;
+                }
+            }
+
+            Event::Paste(text) => self.handle_paste(text),
+
+            Event::Mouse(mouse_event) => self.handle_mouse_event(mouse_event),
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        let text_area = self.painter.get_text_area();
+
+        let boundaries = self.painter.get_text_boundaries();
+
+        let editor = self.buffers.current_mut();
+
+        if !matches!(
+            editor.mode,
+            EditorMode::Normal | EditorMode::Insert | EditorMode::Visual | EditorMode::VisualBlock
+        ) {
+            return;
+        }
+
+        let cell = (
+            mouse_event.row as isize - text_area.y as isize,
+            mouse_event.column.saturating_sub(text_area.x) as usize,
+        );
+
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if !text_area.contains((mouse_event.column, mouse_event.row).into()) {
+                    return;
+                }
+
+                if editor.mode != EditorMode::Insert {
+                    editor.set_mode(EditorMode::Normal);
+                }
+
+                editor.move_to_screen(boundaries, cell);
+
+                let now = Instant::now();
+
+                let position = (editor.position.row, editor.position.column);
+
+                // Quick clicks on the same spot count up to a triple click.
+                let clicks = match self.last_click {
+                    Some((time, (row, column), clicks))
+                        if now.duration_since(time) <= DOUBLE_CLICK_TIME
+                            && (row, column) == position =>
+                    {
+                        clicks % 3 + 1
+                    }
+
+                    _ => 1,
+                };
+
+                match clicks {
+                    2 => editor.select_word(boundaries),
+                    3 => editor.select_line(boundaries),
+                    _ => (),
+                }
+
+                self.last_click = Some((now, position, clicks));
+
+                self.drag_anchor = Some(editor.position);
+            }
+
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let Some(anchor) = self.drag_anchor else {
+                    return;
+                };
+
+                if !matches!(editor.mode, EditorMode::Visual | EditorMode::VisualBlock) {
+                    editor.set_mode(EditorMode::Visual);
+
+                    editor.visual_anchor = Some(anchor);
+                }
+
+                editor.move_to_screen(boundaries, cell);
+            }
+
+            MouseEventKind::Up(MouseButton::Left) => self.drag_anchor = None,
+
+            _ => (),
+        }
+    }
+
+    fn move_offset(&self) -> usize {
+        let repeats = self
+            .consecutive_move_key
+            .map_or(0, |(_, repeats)| repeats as usize);
+
+        let offset =
+            1 + repeats.saturating_sub(SCROLL_ACCELERATION_AFTER) / SCROLL_ACCELERATION_STEP;
+
+        offset.min(self.buffers.current().config.scroll_acceleration)
+    }
+
+    fn handle_paste(&mut self, text: String) {
+        if self.buffers.current().mode != EditorMode::Insert {
+            return;
+        }
+
+        let text_area_boundaries = self.painter.get_text_boundaries();
+
+        self.buffers
+            .current_mut()
+            .paste(text_area_boundaries, &text);
+    }
+
+    fn handle_file_picker_key(&mut self, key_event: KeyEvent, boundaries: Boundaries) {
+        let Some(file_picker) = self.file_picker.as_mut() else {
+            return;
+        };
+
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key_event.code {
+            KeyCode::Char('n') if ctrl => file_picker.move_selection(1),
+
+            KeyCode::Char('p') if ctrl => file_picker.move_selection(-1),
+
+            KeyCode::Down => file_picker.move_selection(1),
+
+            KeyCode::Up => file_picker.move_selection(-1),
+
+            KeyCode::Char(ch) => file_picker.push(ch),
+
+            KeyCode::Backspace => file_picker.pop(),
+
+            KeyCode::Enter => {
+                file_picker.refresh();
+
+                let path = file_picker.selected().map(Path::to_path_buf);
+
+                self.file_picker = None;
+
+                if let Some(path) = path {
+                    if self.buffers.current_mut().open_file(path) {
+                        self.buffers.current_mut().scroll_into_view(boundaries);
+                    }
+                }
+            }
+
+            KeyCode::Esc => self.file_picker = None,
+
+            _ => (),
+        }
+
+        // Each key typed pushes the filtering back until typing pauses.
+        self.file_picker_deadline = self
+            .file_picker
+            .as_ref()
+            .filter(|file_picker| file_picker.is_stale())
+            .map(|_| Instant::now() + FILE_PICKER_DEBOUNCE);
+    }
+
+    fn handle_recent_files_key(&mut self, key_event: KeyEvent, boundaries: Boundaries) {
+        let editor = self.buffers.current_mut();
+
+        let Some(recent_files) = editor.recent_files.as_mut() else {
+            return;
+        };
+
+        match key_event.code {
+            KeyCode::Char('j') | KeyCode::Down => recent_files.move_selection(1),
+
+            KeyCode::Char('k') | KeyCode::Up => recent_files.move_selection(-1),
+
+            KeyCode::Enter => {
+                let path = recent_files.selected().map(Path::to_path_buf);
+
+                editor.recent_files = None;
+
+                if let Some(path) = path {
+                    if editor.load_file(path, false) {
+                        editor.scroll_into_view(boundaries);
+                    }
+                }
+            }
+
+            KeyCode::Esc => editor.recent_files = None,
+
+            _ => (),
+        }
+    }
+
+    fn handle_command_palette_key(&mut self, key_event: KeyEvent) -> Result<()> {
+        let Some(palette) = self.command_palette.as_mut() else {
+            return Ok(());
+        };
+
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key_event.code {
+            KeyCode::Char('n') if ctrl => palette.move_selection(1),
+
+            KeyCode::Char('p') if ctrl => palette.move_selection(-1),
+
+            KeyCode::Down => palette.move_selection(1),
+
+            KeyCode::Up => palette.move_selection(-1),
+
+            KeyCode::Char(ch) => palette.push(ch),
+
+            KeyCode::Backspace => palette.pop(),
+
+            KeyCode::Enter => {
+                let selected = palette.selected();
+
+                self.command_palette = None;
+
+                if let Some(index) = selected {
+                    self.commands
+                        .run(index, self.buffers.current_mut(), &mut self.painter)?;
+
+                    self.buffers
+                        .current_mut()
+                        .scroll_into_view(self.painter.get_text_boundaries());
+                }
+            }
+
+            KeyCode::Esc => self.command_palette = None,
+
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        self.consecutive_move_key = match (key_event.code, self.consecutive_move_key) {
+            (code, Some((previous, repeats))) if code == previous => {
+                Some((code, repeats.saturating_add(1)))
+            }
+            (code @ (KeyCode::Up | KeyCode::Down), _) => Some((code, 1)),
+            _ => None,
+        };
+
+        if self.buffers.current().recovery_pending {
+            match key_event.code {
+                KeyCode::Char('y') => self.buffers.current_mut().answer_recovery(true),
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.buffers.current_mut().answer_recovery(false)
+                }
+                _ => (),
+            }
+
+            return Ok(());
+        }
+
+        if let Some(session) = self.session.take() {
+            match key_event.code {
+                KeyCode::Char('y') => {
+                    self.restore_session(session);
+
+                    return Ok(());
+                }
+
+                KeyCode::Char('n') | KeyCode::Esc => {
+                    self.buffers.current_mut().clear_message();
+
+                    return Ok(());
+                }
+
+                _ => self.buffers.current_mut().clear_message(),
+            }
+        }
+
+        if self.buffers.current().recovery_file.is_some() {
+            if key_event.code == KeyCode::Char('R') {
+                self.buffers.current_mut().answer_recovery_file(true);
+
+                return Ok(());
+            }
+
+            self.buffers.current_mut().answer_recovery_file(false);
+        }
+
+        if self.buffers.current().config.message_timeout == 0 {
+            self.buffers.current_mut().clear_message();
+        }
+
+        if !self.buffers.current().overlay.is_empty() {
+            self.buffers.current_mut().overlay.clear();
+
+            return Ok(());
+        }
+
+        if key_event.code == KeyCode::Esc && self.buffers.current_mut().hover_popup.take().is_some()
+        {
+            return Ok(());
+        }
+
+        let text_area_boundaries = self.painter.get_text_boundaries();
+
+        if self.file_picker.is_some() {
+            self.handle_file_picker_key(key_event, text_area_boundaries);
+
+            return Ok(());
+        }
+
+        if self.buffers.current().recent_files.is_some() {
+            self.handle_recent_files_key(key_event, text_area_boundaries);
+
+            return Ok(());
+        }
+
+        if self.command_palette.is_some() {
+            return self.handle_command_palette_key(key_event);
+        }
+
+        if self.handle_completion_key(key_event, text_area_boundaries) {
+            return Ok(());
+        }
+
+        match key_event.code {
+            KeyCode::F(1) => self
+                .buffers
+                .current_mut()
+                .open_help(&help_text(&self.commands)),
+
+            KeyCode::Down
+                if key_event
+                    .modifiers
+                    .contains(KeyModifiers::ALT | KeyModifiers::SHIFT)
+                    && self.buffers.current().mode == EditorMode::Normal =>
+            {
+                self.buffers
+                    .current_mut()
+                    .duplicate_line(text_area_boundaries);
+            }
+
+            KeyCode::Up
+                if key_event.modifiers.contains(KeyModifiers::ALT)
+                    && self.buffers.current().mode == EditorMode::Normal
+                    && self.buffers.current().position.row > 0 =>
+            {
+                let row = self.buffers.current().position.row;
+
+                self.buffers
+                    .current_mut()
+                    .document_mut()
+                    .swap_rows(row, row - 1);
+
+                self.buffers
+                    .current_mut()
+                    .move_up(text_area_boundaries, 1)?;
+            }
+
+            KeyCode::Down
+                if key_event.modifiers.contains(KeyModifiers::ALT)
+                    && self.buffers.current().mode == EditorMode::Normal
+                    && self.buffers.current().position.row + 1
+                        < self.buffers.current().document().rows.len() =>
+            {
+                let row = self.buffers.current().position.row;
+
+                self.buffers
+                    .current_mut()
+                    .document_mut()
+                    .swap_rows(row, row + 1);
+
+                self.buffers
+                    .current_mut()
+                    .move_down(text_area_boundaries, 1)?;
+            }
+
+            // They browse the history in the search prompt.
+            KeyCode::Up | KeyCode::Down if self.buffers.current().mode == EditorMode::Search => (),
+
+            KeyCode::Up => {
+                let offset = self.move_offset();
+
+                self.buffers
+                    .current_mut()
+                    .move_up(text_area_boundaries, offset)?
+            }
+
+            KeyCode::Down => {
+                let offset = self.move_offset();
+
+                self.buffers
+                    .current_mut()
+                    .move_down(text_area_boundaries, offset)?
+            }
+
+            KeyCode::Left => self
+                .buffers
+                .current_mut()
+                .move_left(text_area_boundaries, 1)?,
+
+            KeyCode::Right => self
+                .buffers
+                .current_mut()
+                .move_right(text_area_boundaries, 1)?,
+
+            KeyCode::Home => self
+                .buffers
+                .current_mut()
+                .move_to_line_start(text_area_boundaries, true)?,
+
+            KeyCode::End => self
+                .buffers
+                .current_mut()
+                .move_to_line_end(text_area_boundaries, true)?,
+
+            _ => (),
+        };
+
+        let count = self.count.take();
+        let pending = std::mem::take(&mut self.pending);
+
+        match self.buffers.current().mode {
+            EditorMode::Normal => match key_event.code {
+                KeyCode::Char('0') if pending == "g" => {
+                    self.buffers
+                        .current_mut()
+                        .move_to_line_start(text_area_boundaries, false)?;
+                }
+
+                KeyCode::Char('$') if pending == "g" => {
+                    self.buffers
+                        .current_mut()
+                        .move_to_line_end(text_area_boundaries, false)?;
+                }
+
+                KeyCode::Char(ch @ ('j' | 'k')) if pending == "g" => {
+                    self.buffers.current_mut().move_display_lines(
+                        text_area_boundaries,
+                        ch == 'j',
+                        count.unwrap_or(1),
+                    )?;
+                }
+
+                KeyCode::Char(ch @ '0'..='9') if ch != '0' || count.is_some() => {
+                    let digit = ch.to_digit(10).unwrap() as usize;
+
+                    self.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+
+                    self.pending = pending;
+                }
+
+                KeyCode::Char(ch) if pending == "d" || pending == "c" => {
+                    let operator = if pending == "d" {
+                        Operator::Delete
+                    } else {
+                        Operator::Change
+                    };
+
+                    let motion = match ch {
+                        'w' => Some(Motion::WordStart),
+                        'e' => Some(Motion::WordEnd),
+                        '0' => Some(Motion::LineStart),
+                        '$' => Some(Motion::LineEnd),
+                        'j' => Some(Motion::Down),
+                        'k' => Some(Motion::Up),
+                        'd' | 'c' if pending.starts_with(ch) => Some(Motion::Line),
+                        _ => None,
+                    };
+
+                    if let Some(motion) = motion {
+                        self.buffers.current_mut().apply_operator(
+                            text_area_boundaries,
+                            operator,
+                            motion,
+                            count.unwrap_or(1),
+                        );
+                    }
+                }
+
+                KeyCode::Char('w')
+                    if key_event.modifiers.contains(KeyModifiers::CONTROL)
+                        && pending.is_empty() =>
+                {
+                    self.pending.push_str("^w");
+                }
+
+                KeyCode::Char(ch) if pending == "^w" => match ch {
+                    's' => self.buffers.current_mut().split(false),
+                    'v' => self.buffers.current_mut().split(true),
+                    'o' => self
+                        .buffers
+                        .current_mut()
+                        .close_split(text_area_boundaries, false),
+                    'c' => self
+                        .buffers
+                        .current_mut()
+                        .close_split(text_area_boundaries, true),
+                    _ => {
+                        if let Some(split) = self.buffers.current().split {
+                            // Directions across the split are ignored.
+                            let towards_first = match ch {
+                                'w' => Some(!split.focus_first),
+                                'h' if split.vertical => Some(true),
+                                'l' if split.vertical => Some(false),
+                                'k' if !split.vertical => Some(true),
+                                'j' if !split.vertical => Some(false),
+                                _ => None,
+                            };
+
+                            if towards_first == Some(!split.focus_first) {
+                                self.buffers
+                                    .current_mut()
+                                    .focus_other_split(text_area_boundaries);
+                            }
+                        }
+                    }
+                },
+
+                KeyCode::Char('g')
+                    if key_event.modifiers.contains(KeyModifiers::CONTROL) && pending == "g" =>
+                {
+                    self.buffers.current_mut().show_stats();
+                }
+
+                KeyCode::Char('g') if pending.is_empty() => {
+                    self.pending.push('g');
+
+                    self.count = count;
+                }
+
+                KeyCode::Char(ch @ (']' | '[')) if pending.is_empty() => self.pending.push(ch),
+
+                KeyCode::Char('z') if pending.is_empty() => self.pending.push('z'),
+
+                KeyCode::Char(ch) if pending == "z" => match ch {
+                    'a' => self.buffers.current_mut().toggle_fold(text_area_boundaries),
+                    'f' => self.buffers.current_mut().create_fold(text_area_boundaries),
+                    'E' => self.buffers.current_mut().delete_folds(),
+                    _ => (),
+                },
+
+                KeyCode::Char('d') if pending == "]" => {
+                    self.buffers
+                        .current_mut()
+                        .next_diagnostic(text_area_boundaries);
+                }
+
+                KeyCode::Char('d') if pending == "[" => {
+                    self.buffers
+                        .current_mut()
+                        .previous_diagnostic(text_area_boundaries);
+                }
+
+                KeyCode::Char('d') if pending == "g" => {
+                    self.request_from_key(LspClient::definition);
+                }
+
+                KeyCode::Char('c') if pending == "g" => {
+                    self.pending = pending + "c";
+
+                    self.count = count;
+                }
+
+                KeyCode::Char('c') if pending == "gc" => {
+                    let start = self.buffers.current().position.row;
+
+                    self.buffers
+                        .current_mut()
+                        .toggle_comment(start, start + count.unwrap_or(1) - 1);
+                }
+
+                KeyCode::Char('q')
+                    if pending.is_empty()
+                        && !key_event.modifiers.contains(KeyModifiers::CONTROL)
+                        && self.buffers.current().is_help()
+                        && self.recording_macro.is_none() =>
+                {
+                    self.buffers.current_mut().close_help();
+                }
+
+                KeyCode::Char('q')
+                    if pending.is_empty()
+                        && !key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    if let Some((register, mut events)) = self.recording_macro.take() {
+                        events.pop();
+
+                        self.macros.insert(register, events);
+                    } else {
+                        self.pending.push('q');
+                    }
+                }
+
+                KeyCode::Char(register) if pending == "q" && register.is_ascii_alphanumeric() => {
+                    self.recording_macro = Some((register, Vec::new()));
+
+                    self.buffers
+                        .current_mut()
+                        .set_message(format!("recording @{}", register));
+                }
+
+                KeyCode::Char('@') if pending.is_empty() => {
+                    self.pending.push('@');
+
+                    self.count = count;
+                }
+
+                KeyCode::Char(register) if pending == "@" => {
+                    self.play_macro(register, count.unwrap_or(1))?;
+                }
+
+                KeyCode::Char(ch @ ('d' | 'c')) if pending.is_empty() => {
+                    self.pending.push(ch);
+
+                    self.count = count;
+                }
+
+                KeyCode::Char('a') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers
+                        .current_mut()
+                        .increment(text_area_boundaries, count.unwrap_or(1) as i64);
+                }
+
+                KeyCode::Char('x') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers
+                        .current_mut()
+                        .increment(text_area_boundaries, -(count.unwrap_or(1) as i64));
+                }
+
+                KeyCode::Char('q') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers.current_mut().status = EditorStatus::Exit;
+                }
+
+                KeyCode::Esc => self.buffers.current_mut().highlight_active = false,
+
+                KeyCode::Char('i') => self.buffers.current_mut().start_insert(),
+
+                KeyCode::Char('K') => {
+                    let row = self.buffers.current().position.row;
+
+                    let messages: Vec<&str> = self
+                        .buffers
+                        .current()
+                        .diagnostics
+                        .iter()
+                        .filter(|diagnostic| diagnostic.row == row)
+                        .map(|diagnostic| diagnostic.message.as_str())
+                        .collect();
+
+                    if messages.is_empty() {
+                        self.request_from_key(LspClient::hover);
+                    } else {
+                        self.buffers.current_mut().hover_popup = Some(messages.join("\n\n"));
+                    }
+                }
+
+                KeyCode::Char('x') => {
+                    self.buffers
+                        .current_mut()
+                        .delete_char(text_area_boundaries, count.unwrap_or(1));
+                }
+
+                KeyCode::Char('.') => {
+                    self.buffers
+                        .current_mut()
+                        .repeat_last_change(text_area_boundaries, count)?;
+                }
+
+                KeyCode::Char('a') => {
+                    let current_row_length = self
+                        .buffers
+                        .current()
+                        .document()
+                        .row_len(self.buffers.current().position.row);
+
+                    if self.buffers.current().position.column
+                        <= current_row_length.saturating_sub(1)
+                    {
+                        self.buffers
+                            .current_mut()
+                            .move_right(text_area_boundaries, 1)?;
+                    }
+
+                    self.buffers.current_mut().set_mode(EditorMode::Insert);
+                }
+
+                KeyCode::Char('o') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Insert);
+
+                    let row = self.buffers.current().position.row;
+
+                    self.buffers
+                        .current_mut()
+                        .document_mut()
+                        .insert_row(row.saturating_add(1));
+
+                    self.buffers
+                        .current_mut()
+                        .move_down(text_area_boundaries, 1)?;
+                }
+
+                KeyCode::Char('O') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Insert);
+
+                    let row = self.buffers.current().position.row;
+
+                    let column = self.buffers.current().position.column;
+
+                    self.buffers.current_mut().document_mut().insert_row(row);
+
+                    self.buffers
+                        .current_mut()
+                        .move_left(text_area_boundaries, column)?;
+                }
+
+                KeyCode::Char('n') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers
+                        .current_mut()
+                        .next_quickfix(text_area_boundaries);
+                }
+
+                // Terminals without the kitty keyboard protocol report
+                // Ctrl+Shift+P as a plain Ctrl+P, which finds a file, so
+                // Alt+P opens the palette on every terminal.
+                KeyCode::Char('P') | KeyCode::Char('p')
+                    if key_event
+                        .modifiers
+                        .contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+                        || key_event.modifiers.contains(KeyModifiers::ALT) =>
+                {
+                    self.command_palette = Some(CommandPalette::new(&self.commands));
+                }
+
+                KeyCode::Char('p') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.file_picker = Some(FilePicker::new(Path::new(".")));
+                }
+
+                KeyCode::Char('p') => self.buffers.current_mut().put(text_area_boundaries),
+
+                KeyCode::Char('u') => self.buffers.current_mut().undo(text_area_boundaries),
+
+                KeyCode::Char('r') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers.current_mut().redo(text_area_boundaries);
+                }
+
+                KeyCode::Char('s') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers.current_mut().save(false);
+                }
+
+                KeyCode::Char('R') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Replace);
+                }
+
+                KeyCode::Char('v') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers.current_mut().set_mode(EditorMode::VisualBlock);
+                }
+
+                KeyCode::Char('v') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Visual);
+                }
+
+                KeyCode::Char(':') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Command);
+                }
+
+                KeyCode::Char('/') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Search);
+                }
+
+                KeyCode::Char('n') => self
+                    .buffers
+                    .current_mut()
+                    .next_match(text_area_boundaries, true),
+
+                KeyCode::Char('N') => self
+                    .buffers
+                    .current_mut()
+                    .next_match(text_area_boundaries, false),
+
+                KeyCode::Char('k') => self
+                    .buffers
+                    .current_mut()
+                    .move_up(text_area_boundaries, 1)?,
+
+                KeyCode::Char('j') => self
+                    .buffers
+                    .current_mut()
+                    .move_down(text_area_boundaries, 1)?,
+
+                KeyCode::Char('h') => self
+                    .buffers
+                    .current_mut()
+                    .move_left(text_area_boundaries, 1)?,
+
+                KeyCode::Char('l') => self
+                    .buffers
+                    .current_mut()
+                    .move_right(text_area_boundaries, 1)?,
+
+                KeyCode::Char('0') => self
+                    .buffers
+                    .current_mut()
+                    .move_to_line_start(text_area_boundaries, true)?,
+
+                KeyCode::Char('$') => self
+                    .buffers
+                    .current_mut()
+                    .move_to_line_end(text_area_boundaries, true)?,
+
+                _ => (),
+            },
+
+            EditorMode::Visual => match key_event.code {
+                KeyCode::Char('g') if pending.is_empty() => {
+                    self.pending.push('g');
+                }
+
+                KeyCode::Char('z') if pending.is_empty() => self.pending.push('z'),
+
+                KeyCode::Char('f' | 'a') if pending == "z" => {
+                    self.buffers.current_mut().create_fold(text_area_boundaries);
+                }
+
+                KeyCode::Char('0') if pending == "g" => {
+                    self.buffers
+                        .current_mut()
+                        .move_to_line_start(text_area_boundaries, false)?;
+                }
+
+                KeyCode::Char('$') if pending == "g" => {
+                    self.buffers
+                        .current_mut()
+                        .move_to_line_end(text_area_boundaries, false)?;
+                }
+
+                KeyCode::Char(ch @ ('j' | 'k')) if pending == "g" => {
+                    self.buffers.current_mut().move_display_lines(
+                        text_area_boundaries,
+                        ch == 'j',
+                        1,
+                    )?;
+                }
+
+                KeyCode::Char('c') if pending == "g" => {
+                    if let Some((start, end)) = self.buffers.current().selected_range() {
+                        self.buffers
+                            .current_mut()
+                            .toggle_comment(start.row, end.row);
+                    }
+
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
+                }
+
+                KeyCode::Char('k') => self
+                    .buffers
+                    .current_mut()
+                    .move_up(text_area_boundaries, 1)?,
+
+                KeyCode::Char('j') => self
+                    .buffers
+                    .current_mut()
+                    .move_down(text_area_boundaries, 1)?,
+
+                KeyCode::Char('h') => self
+                    .buffers
+                    .current_mut()
+                    .move_left(text_area_boundaries, 1)?,
+
+                KeyCode::Char('l') => self
+                    .buffers
+                    .current_mut()
+                    .move_right(text_area_boundaries, 1)?,
+
+                KeyCode::Char('0') => self
+                    .buffers
+                    .current_mut()
+                    .move_to_line_start(text_area_boundaries, true)?,
+
+                KeyCode::Char('$') => self
+                    .buffers
+                    .current_mut()
+                    .move_to_line_end(text_area_boundaries, true)?,
+
+                KeyCode::Char('v') | KeyCode::Esc => {
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
+                }
+
+                KeyCode::Char(':') => {
+                    self.buffers.current_mut().set_mode(EditorMode::Command);
+                }
+
+                _ => (),
+            },
+
+            EditorMode::VisualBlock => match key_event.code {
+                KeyCode::Char('d') => self.buffers.current_mut().delete_block(),
+
+                KeyCode::Char('I') => self.buffers.current_mut().insert_block(),
+
+                KeyCode::Char('k') => self
+                    .buffers
+                    .current_mut()
+                    .move_up(text_area_boundaries, 1)?,
+
+                KeyCode::Char('j') => self
+                    .buffers
+                    .current_mut()
+                    .move_down(text_area_boundaries, 1)?,
+
+                KeyCode::Char('h') => self
+                    .buffers
+                    .current_mut()
+                    .move_left(text_area_boundaries, 1)?,
+
+                KeyCode::Char('l') => self
+                    .buffers
+                    .current_mut()
+                    .move_right(text_area_boundaries, 1)?,
+
+                KeyCode::Char('v') | KeyCode::Esc => {
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
+                }
+
+                _ => (),
+            },
+
+            EditorMode::Command => match key_event.code {
+                KeyCode::Char(ch) => self.buffers.current_mut().command_line.push(ch),
+
+                KeyCode::Backspace if self.buffers.current().command_line.is_empty() => {
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
+                }
+
+                KeyCode::Backspace => {
+                    self.buffers.current_mut().command_line.pop();
+                }
+
+                KeyCode::Enter => self
+                    .buffers
+                    .current_mut()
+                    .execute_command(text_area_boundaries),
+
+                KeyCode::Esc => {
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
+                }
+
+                _ => (),
+            },
+
+            EditorMode::Search => match key_event.code {
+                KeyCode::Char(ch) => {
+                    self.buffers.current_mut().command_line.push(ch);
+
+                    self.buffers
+                        .current_mut()
+                        .preview_search(text_area_boundaries);
+                }
+
+                KeyCode::Backspace if self.buffers.current().command_line.is_empty() => {
+                    self.buffers.current_mut().cancel_search();
+                }
+
+                KeyCode::Backspace => {
+                    self.buffers.current_mut().command_line.pop();
+
+                    self.buffers
+                        .current_mut()
+                        .preview_search(text_area_boundaries);
+                }
+
+                KeyCode::Up => self
+                    .buffers
+                    .current_mut()
+                    .browse_search_history(text_area_boundaries, true),
+
+                KeyCode::Down => self
+                    .buffers
+                    .current_mut()
+                    .browse_search_history(text_area_boundaries, false),
+
+                KeyCode::Enter => self.buffers.current_mut().search(text_area_boundaries),
+
+                KeyCode::Esc => self.buffers.current_mut().cancel_search(),
+
+                _ => (),
+            },
+
+            EditorMode::Replace => match key_event.code {
+                KeyCode::Char(ch) => self
+                    .buffers
+                    .current_mut()
+                    .overwrite(text_area_boundaries, ch)?,
+
+                KeyCode::Backspace => self
+                    .buffers
+                    .current_mut()
+                    .overwrite_backward(text_area_boundaries)?,
+
+                KeyCode::Esc => {
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
+                }
+
+                _ => (),
+            },
+
+            EditorMode::Insert => match key_event.code {
+                KeyCode::Char('w') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers
+                        .current_mut()
+                        .delete_word_before(text_area_boundaries)?;
+                }
+
+                KeyCode::Char('u') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.buffers
+                        .current_mut()
+                        .delete_to_line_start(text_area_boundaries)?;
+                }
+
+                KeyCode::Char(' ') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.request_completion();
+                }
+
+                KeyCode::Char(ch) => {
+                    self.buffers
+                        .current_mut()
+                        .insert(text_area_boundaries, ch)?;
+
+                    if self.triggers_completion(ch) {
+                        self.request_completion();
+                    }
+                }
+
+                KeyCode::Enter => self
+                    .buffers
+                    .current_mut()
+                    .insert(text_area_boundaries, '\n')?,
+
+                KeyCode::Tab => self
+                    .buffers
+                    .current_mut()
+                    .insert_tab(text_area_boundaries)?,
+
+                KeyCode::Delete => {
+                    let position = self.buffers.current().position;
+
+                    self.buffers.current_mut().document_mut().delete(position);
+                }
+
+                KeyCode::Backspace => self
+                    .buffers
+                    .current_mut()
+                    .delete_backward(text_area_boundaries)?,
+
                 KeyCode::Esc => {
-                    self.editor.mode = EditorMode::Normal;
+                    self.buffers.current_mut().set_mode(EditorMode::Normal);
                 }
 
                 _ => (),
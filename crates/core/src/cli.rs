@@ -2,7 +2,97 @@ use clap::Parser;
 
 use std::path::PathBuf;
 
+/// A small modal text editor for the terminal.
 #[derive(Parser)]
+#[command(name = "wind", version, about)]
 pub struct CLI {
-    pub file_path: Option<PathBuf>,
+    /// Files to open, each in its own buffer, or - to read from stdin; a
+    /// new buffer is started when none are given and nothing is piped in
+    pub files: Vec<PathBuf>,
+
+    /// Compare the two files side by side, the second one read-only
+    #[arg(short, long)]
+    pub diff: bool,
+
+    /// Line to place the cursor on
+    #[arg(short, long, value_name = "N")]
+    pub line: Option<usize>,
+
+    /// Refuse to save unless forced with :w!
+    #[arg(short = 'R', long)]
+    pub readonly: bool,
+
+    /// Use this config file instead of ~/.config/wind/config.toml
+    #[arg(short, long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Don't offer to reopen the files from the last session
+    #[arg(long)]
+    pub no_session: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use clap::error::ErrorKind;
+
+    fn parse(args: &[&str]) -> CLI {
+        CLI::try_parse_from([&["wind"], args].concat()).unwrap()
+    }
+
+    #[test]
+    fn no_arguments_open_a_new_buffer() {
+        let cli = parse(&[]);
+
+        assert!(cli.files.is_empty());
+        assert!(!cli.diff && !cli.readonly && !cli.no_session);
+        assert_eq!(cli.line, None);
+        assert_eq!(cli.config, None);
+    }
+
+    #[test]
+    fn every_flag_parses() {
+        let cli = parse(&[
+            "-d",
+            "-l",
+            "12",
+            "-R",
+            "-c",
+            "wind.toml",
+            "--no-session",
+            "a",
+            "b",
+        ]);
+
+        assert_eq!(cli.files, [PathBuf::from("a"), PathBuf::from("b")]);
+        assert!(cli.diff && cli.readonly && cli.no_session);
+        assert_eq!(cli.line, Some(12));
+        assert_eq!(cli.config, Some(PathBuf::from("wind.toml")));
+
+        let cli = parse(&["--diff", "--line=3", "--readonly", "--config", "x", "-"]);
+
+        assert_eq!(cli.files, [PathBuf::from("-")]);
+        assert!(cli.diff && cli.readonly);
+        assert_eq!(cli.line, Some(3));
+        assert_eq!(cli.config, Some(PathBuf::from("x")));
+    }
+
+    #[test]
+    fn version_and_help_are_reported() {
+        let error = |args: &[&str]| {
+            CLI::try_parse_from([&["wind"], args].concat())
+                .err()
+                .unwrap()
+        };
+
+        assert_eq!(error(&["--version"]).kind(), ErrorKind::DisplayVersion);
+
+        let help = error(&["--help"]);
+
+        assert_eq!(help.kind(), ErrorKind::DisplayHelp);
+        assert!(help.to_string().contains("modal text editor"));
+
+        assert_eq!(error(&["--line", "x"]).kind(), ErrorKind::ValueValidation);
+    }
 }
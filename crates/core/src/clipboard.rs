@@ -0,0 +1,66 @@
+use std::env;
+use std::io::{self, Write};
+
+const OSC52_PROGRAMS: &[&str] = &[
+    "iTerm.app",
+    "WezTerm",
+    "kitty",
+    "Alacritty",
+    "ghostty",
+    "tmux",
+    "vscode",
+];
+
+const OSC52_TERMS: &[&str] = &[
+    "xterm-kitty",
+    "alacritty",
+    "foot",
+    "wezterm",
+    "xterm-ghostty",
+    "tmux",
+];
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub struct Osc52Clipboard;
+
+impl Osc52Clipboard {
+    /// The clipboard, when the terminal is one known to relay OSC 52.
+    /// Others may print the sequence or ask about it.
+    pub fn detect() -> Option<Osc52Clipboard> {
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+
+        let term = env::var("TERM").unwrap_or_default();
+
+        (OSC52_PROGRAMS.contains(&program.as_str())
+            || OSC52_TERMS.iter().any(|prefix| term.starts_with(prefix)))
+        .then_some(Osc52Clipboard)
+    }
+
+    pub fn copy(&self, terminal: &mut impl Write, text: &str) -> io::Result<()> {
+        write!(terminal, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+
+        terminal.flush()
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
@@ -0,0 +1,283 @@
+use crate::file_picker::{edit_distance, is_subsequence};
+use crate::painter::Painter;
+
+use anyhow::Result;
+
+use wind_view::editor::{Editor, EditorMode};
+
+type Action = Box<dyn Fn(&mut Editor, &mut Painter) -> Result<()>>;
+
+pub struct Command {
+    pub name: String,
+    pub description: String,
+    pub action: Action,
+}
+
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = CommandRegistry {
+            commands: Vec::new(),
+        };
+
+        registry.register("Save", "Write the buffer to disk", |editor, _| {
+            editor.save(false);
+
+            Ok(())
+        });
+
+        registry.register(
+            "Save (force)",
+            "Write even if the file changed on disk",
+            |editor, _| {
+                editor.save(true);
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Go to line",
+            "Type a line number on the command line",
+            |editor, _| {
+                editor.set_mode(EditorMode::Command);
+
+                Ok(())
+            },
+        );
+
+        registry.register("Undo", "Undo the last change", |editor, painter| {
+            editor.undo(painter.get_text_boundaries());
+
+            Ok(())
+        });
+
+        registry.register("Redo", "Redo the last undone change", |editor, painter| {
+            editor.redo(painter.get_text_boundaries());
+
+            Ok(())
+        });
+
+        registry.register(
+            "Toggle relative line numbers",
+            "Number lines relative to the cursor",
+            |_, painter| {
+                painter.relative_line_numbers = !painter.relative_line_numbers;
+
+                Ok(())
+            },
+        );
+
+        registry.register("Toggle wrap", "Soft-wrap long lines", |editor, _| {
+            editor.config.wrap = !editor.config.wrap;
+
+            Ok(())
+        });
+
+        registry.register(
+            "Toggle cursor line",
+            "Highlight the cursor's line",
+            |editor, _| {
+                editor.config.cursor_line = !editor.config.cursor_line;
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Toggle comment",
+            "Comment or uncomment the current line",
+            |editor, _| {
+                let row = editor.position.row;
+
+                editor.toggle_comment(row, row);
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Duplicate line",
+            "Copy the current line below it",
+            |editor, painter| {
+                editor.duplicate_line(painter.get_text_boundaries());
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Trim trailing whitespace",
+            "Strip spaces at line ends",
+            |editor, painter| {
+                editor.document_mut().trim_trailing_whitespace();
+
+                // The cursor may have been on whitespace that is now gone.
+                let row_len = editor.document().row_len(editor.position.row);
+
+                editor.position.column = editor.position.column.min(row_len);
+
+                editor.scroll_into_view(painter.get_text_boundaries());
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Next diagnostic",
+            "Jump to the next LSP diagnostic",
+            |editor, painter| {
+                editor.next_diagnostic(painter.get_text_boundaries());
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Previous diagnostic",
+            "Jump to the previous LSP diagnostic",
+            |editor, painter| {
+                editor.previous_diagnostic(painter.get_text_boundaries());
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Next quickfix",
+            "Jump to the next quickfix entry",
+            |editor, painter| {
+                editor.next_quickfix(painter.get_text_boundaries());
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "Previous quickfix",
+            "Jump to the previous quickfix entry",
+            |editor, painter| {
+                editor.previous_quickfix(painter.get_text_boundaries());
+
+                Ok(())
+            },
+        );
+
+        registry.register(
+            "File statistics",
+            "Count lines, words and bytes",
+            |editor, _| {
+                editor.show_stats();
+
+                Ok(())
+            },
+        );
+
+        registry
+    }
+}
+
+impl CommandRegistry {
+    pub fn register<F>(&mut self, name: &str, description: &str, action: F)
+    where
+        F: Fn(&mut Editor, &mut Painter) -> Result<()> + 'static,
+    {
+        self.commands.push(Command {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            action: Box::new(action),
+        });
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn run(&self, index: usize, editor: &mut Editor, painter: &mut Painter) -> Result<()> {
+        match self.commands.get(index) {
+            Some(command) => (command.action)(editor, painter),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct CommandPalette {
+    pub query: String,
+    pub matches: Vec<usize>,
+    pub selected: usize,
+    entries: Vec<(String, String)>,
+}
+
+impl CommandPalette {
+    pub fn new(registry: &CommandRegistry) -> CommandPalette {
+        let entries: Vec<(String, String)> = registry
+            .commands()
+            .iter()
+            .map(|command| (command.name.clone(), command.description.clone()))
+            .collect();
+
+        CommandPalette {
+            query: String::new(),
+            matches: (0..entries.len()).collect(),
+            selected: 0,
+            entries,
+        }
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.query.push(ch);
+
+        self.update();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+
+        self.update();
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.matches.len() - 1);
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+
+    pub fn entry(&self, index: usize) -> (&str, &str) {
+        let (name, description) = &self.entries[index];
+
+        (name, description)
+    }
+
+    fn update(&mut self) {
+        let query = self.query.to_lowercase();
+
+        let mut scored: Vec<(usize, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (name, _))| {
+                let name = name.to_lowercase();
+
+                is_subsequence(&query, &name).then(|| (edit_distance(&query, &name), index))
+            })
+            .collect();
+
+        scored.sort();
+
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+
+        self.selected = 0;
+    }
+}
@@ -0,0 +1,94 @@
+use serde_json::Value;
+
+const SNIPPET_FORMAT: u64 = 2;
+
+pub struct CompletionItem {
+    pub label: String,
+    pub detail: Option<String>,
+    pub insert_text: String,
+}
+
+impl CompletionItem {
+    fn from_value(item: &Value) -> Option<CompletionItem> {
+        let label = item["label"].as_str()?.to_owned();
+
+        let text = item["textEdit"]["newText"]
+            .as_str()
+            .or_else(|| item["insertText"].as_str())
+            .unwrap_or(&label);
+
+        let insert_text = if item["insertTextFormat"].as_u64() == Some(SNIPPET_FORMAT) {
+            strip_snippet(text)
+        } else {
+            text.to_owned()
+        };
+
+        Some(CompletionItem {
+            detail: item["detail"].as_str().map(str::to_owned),
+            label,
+            insert_text,
+        })
+    }
+}
+
+pub struct CompletionMenu {
+    pub items: Vec<CompletionItem>,
+    pub selected: usize,
+}
+
+impl CompletionMenu {
+    pub fn from_response(result: &Value) -> Option<CompletionMenu> {
+        let items = result
+            .as_array()
+            .or_else(|| result["items"].as_array())?
+            .iter()
+            .filter_map(CompletionItem::from_value)
+            .collect::<Vec<_>>();
+
+        if items.is_empty() {
+            return None;
+        }
+
+        Some(CompletionMenu { items, selected: 0 })
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let len = self.items.len() as isize;
+
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+
+    pub fn selected(&self) -> Option<&CompletionItem> {
+        self.items.get(self.selected)
+    }
+}
+
+/// Drops tab stops (`$1`, `${2:name}` keeps `name`) since the editor has no
+/// snippet support.
+fn strip_snippet(snippet: &str) -> String {
+    let mut text = String::new();
+
+    let mut chars = snippet.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => text.extend(chars.next()),
+
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+
+                while chars.next_if(char::is_ascii_digit).is_some() {}
+
+                chars.next_if_eq(&':');
+            }
+
+            '$' => while chars.next_if(char::is_ascii_digit).is_some() {},
+
+            '}' => (),
+
+            ch => text.push(ch),
+        }
+    }
+
+    text
+}
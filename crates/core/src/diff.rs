@@ -0,0 +1,109 @@
+use anyhow::Result;
+
+use wind_view::document::Document;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Above this many cells the LCS table is skipped and every line between the
+/// common prefix and suffix is reported as changed.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+pub struct DiffView {
+    pub document: Document,
+    pub changed_left: HashSet<usize>,
+    pub changed_right: HashSet<usize>,
+    revision: Option<u64>,
+}
+
+impl DiffView {
+    pub fn open(path: PathBuf) -> Result<DiffView> {
+        let mut document = Document::open(Some(path))?;
+
+        document.readonly = true;
+
+        Ok(DiffView {
+            document,
+            changed_left: HashSet::new(),
+            changed_right: HashSet::new(),
+            revision: None,
+        })
+    }
+
+    pub fn update(&mut self, left: &Document) {
+        if self.revision == Some(left.revision) {
+            return;
+        }
+
+        self.revision = Some(left.revision);
+
+        let left: Vec<&str> = left.rows.iter().map(|row| row.content.as_str()).collect();
+
+        let right: Vec<&str> = self
+            .document
+            .rows
+            .iter()
+            .map(|row| row.content.as_str())
+            .collect();
+
+        (self.changed_left, self.changed_right) = changed_lines(&left, &right);
+    }
+}
+
+fn changed_lines(left: &[&str], right: &[&str]) -> (HashSet<usize>, HashSet<usize>) {
+    let prefix = left.iter().zip(right).take_while(|(a, b)| a == b).count();
+
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let left_middle = &left[prefix..left.len() - suffix];
+
+    let right_middle = &right[prefix..right.len() - suffix];
+
+    let mut changed_left: HashSet<usize> = (prefix..prefix + left_middle.len()).collect();
+
+    let mut changed_right: HashSet<usize> = (prefix..prefix + right_middle.len()).collect();
+
+    let (n, m) = (left_middle.len(), right_middle.len());
+
+    if n == 0 || m == 0 || n.saturating_mul(m) > MAX_LCS_CELLS {
+        return (changed_left, changed_right);
+    }
+
+    // lengths[i][j] is the LCS length of left_middle[i..] and right_middle[j..].
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if left_middle[i] == right_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+
+    while i < n && j < m {
+        if left_middle[i] == right_middle[j] {
+            changed_left.remove(&(prefix + i));
+
+            changed_right.remove(&(prefix + j));
+
+            i += 1;
+
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (changed_left, changed_right)
+}
@@ -0,0 +1,146 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::{DirEntry, WalkDir};
+
+const MAX_FILES: usize = 10_000;
+const IGNORED_DIRS: [&str; 2] = ["target", "node_modules"];
+
+pub struct FilePicker {
+    pub query: String,
+    pub matches: Vec<PathBuf>,
+    pub selected: usize,
+    files: Vec<PathBuf>,
+    stale: bool,
+}
+
+impl FilePicker {
+    pub fn new(root: &Path) -> FilePicker {
+        let is_hidden = |entry: &DirEntry| {
+            let name = entry.file_name().to_string_lossy();
+
+            entry.depth() > 0
+                && (name.starts_with('.')
+                    || entry.file_type().is_dir() && IGNORED_DIRS.contains(&name.as_ref()))
+        };
+
+        let files: Vec<PathBuf> = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|entry| !is_hidden(entry))
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap_or(entry.path())
+                    .to_path_buf()
+            })
+            .take(MAX_FILES)
+            .collect();
+
+        FilePicker {
+            query: String::new(),
+            matches: files.clone(),
+            selected: 0,
+            files,
+            stale: false,
+        }
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.query.push(ch);
+
+        self.stale = true;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+
+        self.stale = true;
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    pub fn refresh(&mut self) {
+        if std::mem::take(&mut self.stale) {
+            self.update();
+        }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        self.refresh();
+
+        if self.matches.is_empty() {
+            return;
+        }
+
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.matches.len() - 1);
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.matches.get(self.selected).map(PathBuf::as_path)
+    }
+
+    fn update(&mut self) {
+        let query = self.query.to_lowercase();
+
+        let mut scored: Vec<(usize, &PathBuf)> = self
+            .files
+            .iter()
+            .filter_map(|path| {
+                let path_str = path.to_string_lossy().to_lowercase();
+
+                if !is_subsequence(&query, &path_str) {
+                    return None;
+                }
+
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+
+                Some((
+                    edit_distance(&query, &file_name).min(edit_distance(&query, &path_str)),
+                    path,
+                ))
+            })
+            .collect();
+
+        scored.sort_by_key(|(score, path)| (*score, path.as_os_str().len()));
+
+        self.matches = scored.into_iter().map(|(_, path)| path.clone()).collect();
+
+        self.selected = 0;
+    }
+}
+
+pub fn is_subsequence(query: &str, text: &str) -> bool {
+    let mut chars = text.chars();
+
+    query.chars().all(|q| chars.any(|c| c == q))
+}
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
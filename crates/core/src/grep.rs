@@ -0,0 +1,66 @@
+use wind_view::document::Row;
+use wind_view::quickfix::QuickfixEntry;
+
+use anyhow::{bail, Context, Result};
+
+use serde_json::Value;
+
+use tokio::process::Command;
+
+pub async fn grep(pattern: String) -> Result<Vec<QuickfixEntry>> {
+    let output = Command::new("rg")
+        .arg("--json")
+        .arg("--")
+        .arg(&pattern)
+        .output()
+        .await
+        .context("Could not run rg")?;
+
+    if output.status.code() == Some(2) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        bail!("rg: {}", stderr.lines().next().unwrap_or_default());
+    }
+
+    let mut entries = Vec::new();
+
+    for line in output.stdout.split(|b| *b == b'\n') {
+        if line.is_empty() {
+            continue;
+        }
+
+        let message: Value = serde_json::from_slice(line)?;
+
+        if message["type"] != "match" {
+            continue;
+        }
+
+        let data = &message["data"];
+
+        let (Some(path), Some(line_number), Some(text)) = (
+            data["path"]["text"].as_str(),
+            data["line_number"].as_u64(),
+            data["lines"]["text"].as_str(),
+        ) else {
+            continue;
+        };
+
+        let text = text.trim_end_matches(['\r', '\n']);
+
+        let start = data["submatches"][0]["start"].as_u64().unwrap_or(0) as usize;
+
+        let col = text
+            .get(..start)
+            .map(|prefix| Row::from(prefix.to_owned()).len())
+            .unwrap_or(0);
+
+        entries.push(QuickfixEntry {
+            path: path.into(),
+            line: (line_number as usize).saturating_sub(1),
+            col,
+            text: text.to_owned(),
+        });
+    }
+
+    Ok(entries)
+}
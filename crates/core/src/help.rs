@@ -0,0 +1,161 @@
+use crate::commands::CommandRegistry;
+use crate::which_key::{NORMAL_KEYMAP, VISUAL_KEYMAP};
+
+const NORMAL_KEYS: &[(&str, &str)] = &[
+    ("h j k l", "Move left, down, up, right"),
+    (
+        "0 / $, Home / End",
+        "Start / end of the line, or of the wrapped segment",
+    ),
+    ("i", "Insert before the cursor"),
+    ("a", "Insert after the cursor"),
+    ("o / O", "Open a line below / above"),
+    ("R", "Replace mode"),
+    ("v", "Visual mode"),
+    ("Ctrl+v", "Visual block mode"),
+    (":", "Command mode"),
+    ("/", "Search forward"),
+    ("n / N", "Next / previous match"),
+    ("x", "Delete the character under the cursor"),
+    ("p", "Put the last deleted text"),
+    ("u / Ctrl+r", "Undo / redo"),
+    (".", "Repeat the last change"),
+    (
+        "Ctrl+a / Ctrl+x",
+        "Increment / decrement the number under the cursor",
+    ),
+    ("K", "Show diagnostics or hover information"),
+    ("q{reg} ... q", "Record a macro"),
+    ("@{reg} / @@", "Play a macro / the last one"),
+    (
+        "Alt+Up / Alt+Down",
+        "Swap the line with the one above / below",
+    ),
+    ("Alt+Shift+Down", "Duplicate the line"),
+    ("Ctrl+s", "Save"),
+    ("Ctrl+p", "Find a file"),
+    ("Ctrl+Shift+P / Alt+p", "Command palette"),
+    ("Ctrl+n", "Next quickfix entry"),
+    ("Ctrl+q", "Quit"),
+    ("F1", "This help"),
+];
+
+const INSERT_KEYS: &[(&str, &str)] = &[
+    ("Esc", "Back to Normal mode"),
+    ("Tab", "Indent to the next tab stop"),
+    ("Ctrl+w", "Delete the word before the cursor"),
+    ("Ctrl+u", "Delete to the start of the line"),
+    ("Ctrl+Space", "Request completions"),
+    ("Up / Down, Ctrl+n / Ctrl+p", "Choose a completion"),
+    ("Tab / Enter", "Accept a completion"),
+];
+
+const VISUAL_KEYS: &[(&str, &str)] = &[
+    ("h j k l", "Extend the selection"),
+    (":", "Command mode on the selected lines"),
+    ("d", "Delete the block (Visual block)"),
+    ("I", "Insert on every line of the block (Visual block)"),
+    ("v / Esc", "Back to Normal mode"),
+];
+
+const COMMANDS: &[(&str, &str)] = &[
+    (":N", "Go to line N"),
+    (
+        ":w[!] [file]",
+        "Write the file, or to a new name; ! to override changes on disk",
+    ),
+    (":e[!] [file]", "Edit a file, or reload the current one"),
+    (
+        ":bn / :bp",
+        "Next / previous buffer from the file arguments",
+    ),
+    (":set option", "Change an option, e.g. wrap, tabwidth=4"),
+    (":[range]t / :[range]m N", "Copy / move lines below line N"),
+    (
+        ":[range]sort[!] [u]",
+        "Sort lines, ! to reverse, u to drop duplicates",
+    ),
+    (":grep pattern", "Search the project into the quickfix list"),
+    (":cn / :cp", "Next / previous quickfix entry"),
+    (":trim", "Strip trailing whitespace"),
+    (
+        ":[range]retab[!]",
+        "Indent with spaces, ! to indent with tabs",
+    ),
+    (":noh", "Clear the search highlight until the next search"),
+    (":stats", "Show cursor and file statistics"),
+    (":messages", "Show earlier messages"),
+    (
+        ":history /",
+        "Show the search history; Up / Down recall it at /",
+    ),
+    (
+        ":split / :vsplit",
+        "Split the window, stacked / side by side",
+    ),
+    (":only / :close", "Close the other / this window"),
+    (":help", "This help"),
+];
+
+pub fn help_text(registry: &CommandRegistry) -> String {
+    let mut text = String::from("wind keybindings (press q to close)\n");
+
+    let prefixed = |keymap: &[(&'static str, &'static str)]| -> Vec<(String, String)> {
+        keymap
+            .iter()
+            .filter(|(keys, _)| {
+                !keymap
+                    .iter()
+                    .any(|(other, _)| other.len() > keys.len() && other.starts_with(keys))
+            })
+            .map(|(keys, description)| (keys.replace('^', "Ctrl+"), description.to_string()))
+            .collect()
+    };
+
+    let owned = |keys: &[(&str, &str)]| -> Vec<(String, String)> {
+        keys.iter()
+            .map(|(keys, description)| (keys.to_string(), description.to_string()))
+            .collect()
+    };
+
+    let palette = registry
+        .commands()
+        .iter()
+        .map(|command| (command.name.clone(), command.description.clone()))
+        .collect();
+
+    let sections = [
+        (
+            "Normal",
+            [owned(NORMAL_KEYS), prefixed(NORMAL_KEYMAP)].concat(),
+        ),
+        ("Insert", owned(INSERT_KEYS)),
+        (
+            "Visual",
+            [owned(VISUAL_KEYS), prefixed(VISUAL_KEYMAP)].concat(),
+        ),
+        ("Command", owned(COMMANDS)),
+        ("Command palette", palette),
+    ];
+
+    for (title, entries) in sections {
+        text.push_str(&format!("\n{}\n\n", title));
+
+        let width = entries
+            .iter()
+            .map(|(keys, _)| keys.len())
+            .max()
+            .unwrap_or(0);
+
+        for (keys, description) in entries {
+            text.push_str(&format!(
+                "  {:width$}  {}\n",
+                keys,
+                description,
+                width = width
+            ));
+        }
+    }
+
+    text
+}
@@ -1,3 +1,13 @@
 pub mod app;
 pub mod cli;
+pub mod clipboard;
+pub mod commands;
+pub mod completion;
+pub mod diff;
+pub mod file_picker;
+pub mod grep;
+pub mod help;
+pub mod lsp;
 pub mod painter;
+pub mod session;
+pub mod which_key;
@@ -0,0 +1,403 @@
+use anyhow::{bail, Context, Result};
+
+use serde_json::{json, Value};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+
+pub enum LspEvent {
+    Response {
+        id: u64,
+        result: Result<Value, String>,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
+    Exited,
+}
+
+pub struct LspClient {
+    pub language_id: String,
+    pub initialized: bool,
+    outgoing: UnboundedSender<Value>,
+    pending: HashMap<u64, String>,
+    next_id: u64,
+    versions: HashMap<String, i32>,
+    _child: Child,
+}
+
+impl LspClient {
+    pub fn start(
+        command: &str,
+        language_id: &str,
+        root: &Path,
+        events: UnboundedSender<LspEvent>,
+    ) -> Result<LspClient> {
+        let mut args = command.split_whitespace();
+
+        let Some(program) = args.next() else {
+            bail!("Empty language server command");
+        };
+
+        let mut child = Command::new(program)
+            .args(args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Could not start {}", program))?;
+
+        let stdin = child.stdin.take().context("Language server has no stdin")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("Language server has no stdout")?;
+
+        let (outgoing, outgoing_receiver) = unbounded_channel();
+
+        tokio::spawn(write_messages(stdin, outgoing_receiver));
+
+        tokio::spawn(read_messages(stdout, outgoing.clone(), events));
+
+        let mut client = LspClient {
+            language_id: language_id.to_owned(),
+            initialized: false,
+            outgoing,
+            pending: HashMap::new(),
+            next_id: 0,
+            versions: HashMap::new(),
+            _child: child,
+        };
+
+        let root_uri = path_to_uri(root);
+
+        client.request(
+            "initialize",
+            json!({
+                "processId": process::id(),
+                "rootUri": root_uri,
+                "workspaceFolders": [{ "uri": root_uri, "name": "root" }],
+                "capabilities": {
+                    "textDocument": {
+                        "synchronization": { "didSave": true },
+                        "completion": { "completionItem": { "snippetSupport": false } },
+                    },
+                },
+            }),
+        );
+
+        Ok(client)
+    }
+
+    pub fn request(&mut self, method: &str, params: Value) -> u64 {
+        self.next_id += 1;
+
+        self.pending.insert(self.next_id, method.to_owned());
+
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        }));
+
+        self.next_id
+    }
+
+    pub fn notify(&self, method: &str, params: Value) {
+        let _ = self.outgoing.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    pub fn take_request(&mut self, id: u64) -> Option<String> {
+        self.pending.remove(&id)
+    }
+
+    pub fn hover(&mut self, path: &Path, line: usize, character: usize) -> u64 {
+        self.request(
+            "textDocument/hover",
+            json!({
+                "textDocument": { "uri": path_to_uri(path) },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    pub fn completion(&mut self, path: &Path, line: usize, character: usize) -> u64 {
+        self.request(
+            "textDocument/completion",
+            json!({
+                "textDocument": { "uri": path_to_uri(path) },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    pub fn definition(&mut self, path: &Path, line: usize, character: usize) -> u64 {
+        self.request(
+            "textDocument/definition",
+            json!({
+                "textDocument": { "uri": path_to_uri(path) },
+                "position": { "line": line, "character": character },
+            }),
+        )
+    }
+
+    pub fn did_open(&mut self, path: &Path, text: String) {
+        let uri = path_to_uri(path);
+
+        self.versions.insert(uri.clone(), 0);
+
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": self.language_id,
+                    "version": 0,
+                    "text": text,
+                },
+            }),
+        );
+    }
+
+    pub fn did_change(&mut self, path: &Path, text: String) {
+        let uri = path_to_uri(path);
+
+        let version = self.versions.entry(uri.clone()).or_default();
+
+        *version += 1;
+
+        let version = *version;
+
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": [{ "text": text }],
+            }),
+        );
+    }
+
+    pub fn did_save(&self, path: &Path) {
+        self.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": path_to_uri(path) } }),
+        );
+    }
+
+    pub fn did_close(&mut self, path: &Path) {
+        let uri = path_to_uri(path);
+
+        self.versions.remove(&uri);
+
+        self.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        );
+    }
+}
+
+pub fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(hover_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(object) => object
+            .get("value")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned(),
+        _ => String::new(),
+    }
+}
+
+/// Flattens a `Location`, `Location[]` or `LocationLink[]` result into
+/// `(path, line, utf16 character)` triples.
+pub fn locations(result: &Value) -> Vec<(PathBuf, usize, usize)> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        Value::Null => &[],
+        location => std::slice::from_ref(location),
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let uri = item["uri"]
+                .as_str()
+                .or_else(|| item["targetUri"].as_str())?;
+
+            let range = if item["range"].is_object() {
+                &item["range"]
+            } else {
+                &item["targetSelectionRange"]
+            };
+
+            Some((
+                uri_to_path(uri)?,
+                range["start"]["line"].as_u64()? as usize,
+                range["start"]["character"].as_u64()? as usize,
+            ))
+        })
+        .collect()
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+
+    let mut i = 0;
+
+    while i < encoded.len() {
+        let decoded = match encoded[i] {
+            b'%' => std::str::from_utf8(encoded.get(i + 1..i + 3)?)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+
+                i += 3;
+            }
+
+            None => {
+                bytes.push(encoded[i]);
+
+                i += 1;
+            }
+        }
+    }
+
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+    let mut uri = String::from("file://");
+
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+async fn write_messages(mut stdin: ChildStdin, mut outgoing: UnboundedReceiver<Value>) {
+    while let Some(message) = outgoing.recv().await {
+        let body = message.to_string();
+
+        let frame = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+
+        if stdin.write_all(frame.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn read_messages(
+    stdout: ChildStdout,
+    outgoing: UnboundedSender<Value>,
+    events: UnboundedSender<LspEvent>,
+) {
+    let mut reader = BufReader::new(stdout);
+
+    while let Ok(Some(message)) = read_message(&mut reader).await {
+        let id = message.get("id").cloned();
+
+        let method = message["method"].as_str().map(str::to_owned);
+
+        let event = match (id, method) {
+            (Some(id), Some(_)) => {
+                let _ = outgoing.send(json!({ "jsonrpc": "2.0", "id": id, "result": null }));
+
+                continue;
+            }
+
+            (Some(id), None) => {
+                let Some(id) = id.as_u64() else {
+                    continue;
+                };
+
+                let result = match message.get("error") {
+                    Some(error) => Err(error["message"].as_str().unwrap_or_default().to_owned()),
+                    None => Ok(message["result"].clone()),
+                };
+
+                LspEvent::Response { id, result }
+            }
+
+            (None, Some(method)) => LspEvent::Notification {
+                method,
+                params: message["params"].clone(),
+            },
+
+            (None, None) => continue,
+        };
+
+        if events.send(event).is_err() {
+            return;
+        }
+    }
+
+    let _ = events.send(LspEvent::Exited);
+}
+
+async fn read_message(reader: &mut BufReader<ChildStdout>) -> Result<Option<Value>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if reader.read_line(&mut header).await? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        bail!("Missing Content-Length header");
+    };
+
+    let mut body = vec![0; content_length];
+
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
@@ -1,4 +1,15 @@
-use wind_view::editor::Editor;
+use crate::commands::CommandPalette;
+use crate::completion::CompletionMenu;
+use crate::diff::DiffView;
+use crate::file_picker::FilePicker;
+
+use wind_view::boundaries::Boundaries;
+use wind_view::diagnostics::DiagnosticSeverity;
+use wind_view::document::Row;
+use wind_view::editor::{Editor, EditorMode, EditorStatus};
+use wind_view::git::DiffKind;
+use wind_view::position::Position;
+use wind_view::recent::RecentFiles;
 
 use anyhow::Result;
 
@@ -7,9 +18,17 @@ use ratatui::layout::*;
 use ratatui::style::{Color, Stylize};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::*;
-use ratatui::Terminal;
+use ratatui::{Frame, Terminal};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 use std::env;
+use std::time::Instant;
+
+const HOVER_MAX_WIDTH: u16 = 60;
+const COMPLETION_MAX_WIDTH: u16 = 50;
+const COMPLETION_MAX_ITEMS: usize = 10;
 
 pub struct Palette {
     pub text_area_fg: Color,
@@ -17,6 +36,19 @@ pub struct Palette {
     pub line_numbers_fg: Color,
     pub status_bar_fg: Color,
     pub status_bar_bg: Color,
+    pub selection_bg: Color,
+    pub search_highlight_bg: Color,
+    pub search_failed_fg: Color,
+    pub whitespace_fg: Color,
+    pub fold_fg: Color,
+    pub cursor_line_bg: Color,
+    pub diff_added_fg: Color,
+    pub diff_modified_fg: Color,
+    pub diff_removed_fg: Color,
+    pub diagnostic_error_fg: Color,
+    pub diagnostic_warning_fg: Color,
+    pub diagnostic_info_fg: Color,
+    pub diff_changed_bg: Color,
 }
 
 impl Default for Palette {
@@ -27,20 +59,47 @@ impl Default for Palette {
             line_numbers_fg: Color::White,
             status_bar_fg: Color::Black,
             status_bar_bg: Color::White,
+            selection_bg: Color::DarkGray,
+            search_highlight_bg: Color::Indexed(58),
+            search_failed_fg: Color::LightRed,
+            whitespace_fg: Color::Indexed(240),
+            fold_fg: Color::Cyan,
+            cursor_line_bg: Color::Indexed(236),
+            diff_added_fg: Color::Green,
+            diff_modified_fg: Color::Yellow,
+            diff_removed_fg: Color::Red,
+            diagnostic_error_fg: Color::LightRed,
+            diagnostic_warning_fg: Color::LightYellow,
+            diagnostic_info_fg: Color::LightBlue,
+            diff_changed_bg: Color::Indexed(52),
         }
     }
 }
 
 pub struct Painter {
-    areas: [Rect; 6],
+    areas: [Rect; 7],
+    diff_area: Option<Rect>,
+    split_areas: Option<([Rect; 3], Rect)>,
+    split_layout: Option<(bool, bool)>,
+    boundaries: Boundaries,
     palette: Palette,
+    pub relative_line_numbers: bool,
+    pub diff: Option<DiffView>,
+    pub buffer_position: Option<(usize, usize)>,
 }
 
 impl Painter {
-    pub fn new(boundaries: Rect) -> Painter {
+    pub fn new(boundaries: Boundaries) -> Painter {
         let mut painter = Painter {
-            areas: [Rect::default(); 6],
+            areas: [Rect::default(); 7],
+            diff_area: None,
+            split_areas: None,
+            split_layout: None,
+            boundaries,
             palette: Palette::default(),
+            relative_line_numbers: env::var("WIND_RELATIVE_LINE_NUMBERS").is_ok(),
+            diff: None,
+            buffer_position: None,
         };
 
         painter.recompute_areas(boundaries);
@@ -48,23 +107,88 @@ impl Painter {
         painter
     }
 
-    pub fn recompute_areas(&mut self, boundaries: Rect) {
+    pub fn recompute_areas(&mut self, boundaries: Boundaries) {
+        self.boundaries = boundaries;
+
+        let boundaries = Rect::new(0, 0, boundaries.width, boundaries.height);
+
         let main_layout = Layout::new(
             Direction::Vertical,
+            // Min(1) outranks Length, so on very short terminals the
+            // message line and then the status bar give way to the text.
             [
-                Constraint::Percentage(95),
-                Constraint::Percentage(2),
-                Constraint::Percentage(3),
+                Constraint::Min(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
             ],
         );
 
         let main_areas = main_layout.split(boundaries);
 
-        let text_area = Layout::new(
+        // In diff mode the right half shows the other file, starting with a
+        // one-column separator.
+        let editor_area = if self.diff.is_some() {
+            let halves = Layout::new(
+                Direction::Horizontal,
+                [Constraint::Fill(1), Constraint::Fill(1)],
+            )
+            .split(main_areas[0]);
+
+            self.diff_area = Some(halves[1]);
+
+            halves[0]
+        } else {
+            self.diff_area = None;
+
+            main_areas[0]
+        };
+
+        let (editor_area, split_area) = match self.split_layout {
+            Some((vertical, focus_first)) => {
+                let direction = if vertical {
+                    Direction::Horizontal
+                } else {
+                    Direction::Vertical
+                };
+
+                let panes = Layout::new(
+                    direction,
+                    [
+                        Constraint::Fill(1),
+                        Constraint::Length(1),
+                        Constraint::Fill(1),
+                    ],
+                )
+                .split(editor_area);
+
+                let (focused, unfocused) = if focus_first {
+                    (panes[0], panes[2])
+                } else {
+                    (panes[2], panes[0])
+                };
+
+                (focused, Some((unfocused, panes[1])))
+            }
+
+            None => (editor_area, None),
+        };
+
+        let pane_layout = Layout::new(
             Direction::Horizontal,
-            [Constraint::Max(5), Constraint::Min(1)],
-        )
-        .split(main_areas[0]);
+            [
+                Constraint::Max(5),
+                Constraint::Length(1),
+                Constraint::Min(1),
+            ],
+        );
+
+        self.split_areas = split_area.map(|(area, separator)| {
+            let pane = pane_layout.split(area);
+
+            ([pane[0], pane[1], pane[2]], separator)
+        });
+
+        let text_area = pane_layout.split(editor_area);
 
         let status_bar_area = Layout::new(
             Direction::Horizontal,
@@ -75,14 +199,31 @@ impl Painter {
 
         self.areas = [
             text_area[0],
-            text_area[1],
+            text_area[2],
             status_bar_area[0],
             status_bar_area[1],
             status_bar_area[2],
             main_areas[2],
+            text_area[1],
         ];
     }
 
+    pub fn update_split(&mut self, editor: &Editor) -> bool {
+        let layout = editor
+            .split
+            .map(|split| (split.vertical, split.focus_first));
+
+        if layout == self.split_layout {
+            return false;
+        }
+
+        self.split_layout = layout;
+
+        self.recompute_areas(self.boundaries);
+
+        true
+    }
+
     #[inline]
     pub fn get_line_numbers_area(&self) -> Rect {
         self.areas[0]
@@ -93,81 +234,622 @@ impl Painter {
         self.areas[1]
     }
 
+    #[inline]
+    pub fn get_text_boundaries(&self) -> Boundaries {
+        Boundaries::new(self.areas[1].width, self.areas[1].height)
+    }
+
+    #[inline]
+    pub fn get_gutter_area(&self) -> Rect {
+        self.areas[6]
+    }
+
     #[inline]
     pub fn get_status_bar_area(&self) -> [Rect; 4] {
         [self.areas[2], self.areas[3], self.areas[4], self.areas[5]]
     }
 
-    pub fn paint<T: TerminalBackend>(
+    fn render_row(
         &self,
-        terminal: &mut Terminal<T>,
         editor: &Editor,
-    ) -> Result<()> {
-        let text_area = self.get_text_area();
+        index: usize,
+        row: &Row,
+        (start, end): (usize, usize),
+        focused: bool,
+    ) -> Line<'static> {
+        let mut highlights: Vec<(usize, usize, Color)> = editor
+            .search_matches_in_row(index)
+            .filter(|_| editor.highlight_active)
+            .map(|(from, to)| (from, to, self.palette.search_highlight_bg))
+            .collect();
 
-        let text_block = Block::default()
-            .fg(self.palette.text_area_fg)
-            .bg(self.palette.text_area_bg);
+        if let Some((from, to)) = editor.selected_columns(index).filter(|_| focused) {
+            highlights.push((from, to, self.palette.selection_bg));
+        }
+
+        let list = editor.config.list;
+
+        let tab_width = editor.config.tab_width;
+
+        let trailing_start =
+            row.len() - (row.content.len() - row.content.trim_end_matches(' ').len());
 
-        let line_start = editor.scroll_offset.column;
+        let tabs: Vec<usize> = match list {
+            true => row
+                .content
+                .graphemes(true)
+                .enumerate()
+                .filter(|(_, grapheme)| *grapheme == "\t")
+                .map(|(column, _)| column)
+                .collect(),
+            false => Vec::new(),
+        };
+
+        let mut cuts = vec![start, end];
+
+        for (from, to, _) in &highlights {
+            cuts.push((*from).clamp(start, end));
+
+            cuts.push((*to).clamp(start, end));
+        }
+
+        if list {
+            cuts.push(trailing_start.clamp(start, end));
+
+            for tab in &tabs {
+                cuts.push((*tab).clamp(start, end));
+
+                cuts.push((tab + 1).clamp(start, end));
+            }
+        }
 
-        let line_end = editor
-            .scroll_offset
+        cuts.sort_unstable();
+
+        cuts.dedup();
+
+        let mut spans: Vec<Span> = cuts
+            .windows(2)
+            .map(|cut| {
+                let text = row.render_expanded(cut[0], cut[1], tab_width);
+
+                // A tab keeps the cells it takes, with the marker in the
+                // first, so the cursor still lines up after it.
+                let span = if list && tabs.contains(&cut[0]) {
+                    Span::from(format!("→{}", &text[1..])).fg(self.palette.whitespace_fg)
+                } else if list && cut[0] >= trailing_start {
+                    Span::from(text.replace(' ', "·")).fg(self.palette.whitespace_fg)
+                } else {
+                    Span::from(text)
+                };
+
+                // Later highlights, i.e. the selection, win.
+                match highlights
+                    .iter()
+                    .rev()
+                    .find(|(from, to, _)| (*from..*to).contains(&cut[0]))
+                {
+                    Some((_, _, bg)) => span.bg(*bg),
+                    None => span,
+                }
+            })
+            .collect();
+
+        if list && (start..=end).contains(&row.len()) {
+            spans.push(Span::from("$").fg(self.palette.whitespace_fg));
+        }
+
+        Line::from(spans)
+    }
+
+    fn render_view(
+        &self,
+        f: &mut Frame,
+        editor: &Editor,
+        position: Position,
+        scroll_offset: Position,
+        [line_numbers_area, gutter_area, text_area]: [Rect; 3],
+        focused: bool,
+    ) {
+        let line_start = scroll_offset.column;
+
+        let line_end = scroll_offset
             .column
             .saturating_add(text_area.width as usize);
 
-        let lines: Vec<(Rect, Line)> = editor
+        let mut lines: Vec<(Rect, Line, usize, bool)> = Vec::new();
+
+        for (index, row) in editor
+            .document()
+            .rows
+            .iter()
+            .enumerate()
+            .skip(scroll_offset.row)
+        {
+            if lines.len() >= text_area.height as usize {
+                break;
+            }
+
+            if editor.folds.is_hidden(index) {
+                continue;
+            }
+
+            // A closed fold shows as one summary line.
+            if let Some(fold) = editor.folds.closed_at(index) {
+                let summary = format!(
+                    "+--{:>3} lines folded: {}",
+                    fold.end - fold.start + 1,
+                    row.content.trim()
+                );
+
+                lines.push((
+                    Rect::new(
+                        text_area.x,
+                        text_area.y + lines.len() as u16,
+                        text_area.width,
+                        1,
+                    ),
+                    Line::from(summary).fg(self.palette.fold_fg),
+                    index,
+                    true,
+                ));
+
+                continue;
+            }
+
+            let (starts, indent) = if editor.config.wrap {
+                editor.wrap_row(row, text_area.width as usize)
+            } else {
+                (vec![line_start], 0)
+            };
+
+            for (i, start) in starts.iter().enumerate() {
+                if lines.len() >= text_area.height as usize {
+                    break;
+                }
+
+                let end = if editor.config.wrap {
+                    starts.get(i + 1).copied().unwrap_or(row.len())
+                } else {
+                    line_end
+                };
+
+                let mut line = self.render_row(editor, index, row, (*start, end), focused);
+
+                // Continuation lines are indented by `wrap_indent`.
+                if i > 0 && indent > 0 {
+                    line.spans.insert(0, Span::raw(" ".repeat(indent)));
+                }
+
+                lines.push((
+                    Rect::new(
+                        text_area.x,
+                        text_area.y + lines.len() as u16,
+                        text_area.width,
+                        1,
+                    ),
+                    line,
+                    index,
+                    i == 0,
+                ));
+            }
+        }
+
+        let line_numbers_block = Block::default()
+            .fg(self.palette.line_numbers_fg)
+            .bg(self.palette.text_area_bg);
+
+        let line_number = |index: usize| {
+            if self.relative_line_numbers && index != position.row {
+                index.abs_diff(position.row)
+            } else {
+                index + 1
+            }
+        };
+
+        f.render_widget(line_numbers_block, line_numbers_area);
+
+        for (line_rect, line, index, is_first_segment) in lines {
+            let line_number = if is_first_segment {
+                line_number(index).to_string()
+            } else {
+                String::new()
+            };
+
+            let mut line_number_paragraph = Paragraph::new(line_number).centered();
+
+            let mut line_paragraph = Paragraph::new(line);
+
+            if self
+                .diff
+                .as_ref()
+                .is_some_and(|diff| diff.changed_left.contains(&index))
+            {
+                line_paragraph = line_paragraph.bg(self.palette.diff_changed_bg);
+            }
+
+            if editor.config.cursor_line && index == position.row {
+                line_number_paragraph = line_number_paragraph.bg(self.palette.cursor_line_bg);
+
+                line_paragraph = line_paragraph.bg(self.palette.cursor_line_bg);
+            }
+
+            f.render_widget(
+                line_number_paragraph,
+                Rect::new(line_numbers_area.x, line_rect.y, line_numbers_area.width, 1),
+            );
+
+            let diff_kind = editor.git_diff.get(index).filter(|_| is_first_segment);
+
+            let diagnostic = editor.diagnostic_at(index).filter(|_| is_first_segment);
+
+            let gutter_paragraph = match (diagnostic, diff_kind) {
+                (Some(diagnostic), _) => {
+                    let fg = match diagnostic.severity {
+                        DiagnosticSeverity::Error => self.palette.diagnostic_error_fg,
+                        DiagnosticSeverity::Warning => self.palette.diagnostic_warning_fg,
+                        _ => self.palette.diagnostic_info_fg,
+                    };
+
+                    Paragraph::new(diagnostic.severity.symbol()).fg(fg)
+                }
+                (None, diff_kind) => match diff_kind {
+                    Some(DiffKind::Added) => Paragraph::new("+").fg(self.palette.diff_added_fg),
+                    Some(DiffKind::Modified) => {
+                        Paragraph::new("~").fg(self.palette.diff_modified_fg)
+                    }
+                    Some(DiffKind::Removed) => {
+                        Paragraph::new("\u{2212}").fg(self.palette.diff_removed_fg)
+                    }
+                    None => Paragraph::new(""),
+                },
+            };
+
+            f.render_widget(
+                gutter_paragraph,
+                Rect::new(gutter_area.x, line_rect.y, gutter_area.width, 1),
+            );
+
+            f.render_widget(line_paragraph, line_rect);
+        }
+    }
+
+    fn render_diff_pane(&self, f: &mut Frame, editor: &Editor, diff: &DiffView, area: Rect) {
+        let block = Block::new()
+            .borders(Borders::LEFT)
+            .fg(self.palette.line_numbers_fg)
+            .bg(self.palette.text_area_bg);
+
+        let inner = block.inner(area);
+
+        f.render_widget(block, area);
+
+        let scroll_offset = editor.scroll_offset();
+
+        let end = scroll_offset.column.saturating_add(inner.width as usize);
+
+        for (y, (index, row)) in diff
             .document
             .rows
             .iter()
-            .skip(editor.scroll_offset.row)
             .enumerate()
-            .filter(|(i, _)| *i < text_area.height as usize)
-            .map(|(i, r)| {
-                (
-                    Rect::new(text_area.x, text_area.y + i as u16, text_area.width, 1),
-                    Line::from(Span::from(r.render(line_start, line_end))),
-                )
+            .skip(scroll_offset.row)
+            .take(inner.height as usize)
+            .enumerate()
+        {
+            let text = row.render_expanded(scroll_offset.column, end, editor.config.tab_width);
+
+            let mut line_paragraph = Paragraph::new(text).fg(self.palette.text_area_fg);
+
+            if diff.changed_right.contains(&index) {
+                line_paragraph = line_paragraph.bg(self.palette.diff_changed_bg);
+            }
+
+            f.render_widget(
+                line_paragraph,
+                Rect::new(inner.x, inner.y + y as u16, inner.width, 1),
+            );
+        }
+    }
+
+    fn render_hover(&self, f: &mut Frame, hover: &str, area: Rect, cursor_y: u16) {
+        let width = area.width.min(HOVER_MAX_WIDTH);
+
+        let lines: Vec<Line> = hover
+            .lines()
+            .flat_map(|line| {
+                let row = Row::from(line.to_owned());
+
+                let starts = row.wrap(width.saturating_sub(2) as usize, 0);
+
+                (0..starts.len())
+                    .map(|i| {
+                        let end = starts.get(i + 1).copied().unwrap_or(row.len());
+
+                        Line::from(row.render(starts[i], end))
+                    })
+                    .collect::<Vec<_>>()
             })
             .collect();
 
-        let line_numbers_area = self.get_line_numbers_area();
+        let space_below = area.height.saturating_sub(cursor_y + 1);
 
-        let line_numbers_block = Block::default()
-            .fg(self.palette.line_numbers_fg)
+        let space_above = cursor_y;
+
+        let wanted = lines.len() as u16 + 2;
+
+        let (y, height) = if wanted <= space_below || space_below >= space_above {
+            (area.y + cursor_y + 1, wanted.min(space_below))
+        } else {
+            let height = wanted.min(space_above);
+
+            (area.y + cursor_y - height, height)
+        };
+
+        if height < 3 {
+            return;
+        }
+
+        let hover_area = Rect::new(area.x, y, width, height);
+
+        let hover_paragraph = Paragraph::new(lines)
+            .block(Block::bordered())
+            .fg(self.palette.text_area_fg)
             .bg(self.palette.text_area_bg);
 
-        let mut line_numbers = Vec::new();
+        f.render_widget(Clear, hover_area);
 
-        if env::var("WIND_RELATIVE_LINE_NUMBERS").is_ok() {
-            let mut n = lines
-                .iter()
-                .enumerate()
-                .position(|(i, _)| i == editor.position.row - editor.scroll_offset.row)
-                .unwrap();
+        f.render_widget(hover_paragraph, hover_area);
+    }
+
+    fn render_completion(
+        &self,
+        f: &mut Frame,
+        completion: &CompletionMenu,
+        area: Rect,
+        (cursor_y, cursor_x): (usize, usize),
+    ) {
+        let cursor_y = cursor_y as u16;
+
+        let labels: Vec<String> = completion
+            .items
+            .iter()
+            .map(|item| match &item.detail {
+                Some(detail) => format!("{}  {}", item.label, detail),
+                None => item.label.clone(),
+            })
+            .collect();
+
+        let content_width = labels
+            .iter()
+            .map(|label| Row::from(label.clone()).len())
+            .max()
+            .unwrap_or(0) as u16;
+
+        let width = (content_width + 2)
+            .min(COMPLETION_MAX_WIDTH)
+            .min(area.width);
+
+        let wanted = completion.items.len().min(COMPLETION_MAX_ITEMS) as u16 + 2;
+
+        let space_below = area.height.saturating_sub(cursor_y + 1);
+
+        let (y, height) = if wanted <= space_below || space_below >= cursor_y {
+            (area.y + cursor_y + 1, wanted.min(space_below))
+        } else {
+            let height = wanted.min(cursor_y);
+
+            (area.y + cursor_y - height, height)
+        };
+
+        if height < 3 {
+            return;
+        }
+
+        let x = area.x + (cursor_x as u16).min(area.width - width);
+
+        let list_height = height.saturating_sub(2) as usize;
 
-            let mut increment = false;
+        let skip = (completion.selected + 1).saturating_sub(list_height);
 
-            for _ in 0..lines.len() {
-                if n == 0 {
-                    line_numbers.push(editor.position.row + 1);
+        let lines: Vec<Line> = labels
+            .into_iter()
+            .enumerate()
+            .skip(skip)
+            .take(list_height)
+            .map(|(i, label)| {
+                let line = Line::from(label);
 
-                    increment = true;
+                if i == completion.selected {
+                    line.bg(self.palette.selection_bg)
                 } else {
-                    line_numbers.push(n);
+                    line
                 }
+            })
+            .collect();
+
+        let completion_area = Rect::new(x, y, width, height);
+
+        let completion_paragraph = Paragraph::new(lines)
+            .block(Block::bordered())
+            .fg(self.palette.text_area_fg)
+            .bg(self.palette.text_area_bg);
+
+        f.render_widget(Clear, completion_area);
+
+        f.render_widget(completion_paragraph, completion_area);
+    }
+
+    fn render_which_key(&self, f: &mut Frame, continuations: &[(String, String)], area: Rect) {
+        let label_width = continuations
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0);
+
+        let lines: Vec<Line> = continuations
+            .iter()
+            .map(|(label, description)| {
+                Line::from(vec![
+                    Span::raw(format!("{:>width$}", label, width = label_width))
+                        .fg(self.palette.diagnostic_info_fg),
+                    Span::raw(format!("  {}", description)),
+                ])
+            })
+            .collect();
+
+        let content_width = lines.iter().map(Line::width).max().unwrap_or(0) as u16;
 
-                if increment {
-                    n += 1;
+        let width = (content_width + 2).min(area.width);
+
+        let height = (lines.len() as u16 + 2).min(area.height);
+
+        if height < 3 {
+            return;
+        }
+
+        let which_key_area = Rect::new(
+            area.x + area.width - width,
+            area.y + area.height - height,
+            width,
+            height,
+        );
+
+        let which_key_paragraph = Paragraph::new(lines)
+            .block(Block::bordered())
+            .fg(self.palette.text_area_fg)
+            .bg(self.palette.text_area_bg);
+
+        f.render_widget(Clear, which_key_area);
+
+        f.render_widget(which_key_paragraph, which_key_area);
+    }
+
+    fn render_picker<'a>(
+        &self,
+        f: &mut Frame,
+        title: &str,
+        query: &str,
+        (len, selected): (usize, usize),
+        entry: impl Fn(usize) -> Line<'a>,
+        area: Rect,
+    ) {
+        let width = (area.width * 3 / 5).max(area.width.min(20));
+        let height = (area.height * 3 / 5).max(area.height.min(5));
+
+        let picker_area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let list_height = height.saturating_sub(3) as usize;
+
+        let skip = (selected + 1).saturating_sub(list_height);
+
+        let mut lines = vec![Line::from(format!("> {}", query))];
+
+        lines.extend((skip..len).take(list_height).map(|i| {
+            let line = entry(i);
+
+            if i == selected {
+                line.bg(self.palette.selection_bg)
+            } else {
+                line
+            }
+        }));
+
+        let picker_paragraph = Paragraph::new(lines)
+            .block(Block::bordered().title(format!(" {} ({}) ", title, len)))
+            .fg(self.palette.text_area_fg)
+            .bg(self.palette.text_area_bg);
+
+        f.render_widget(Clear, picker_area);
+
+        f.render_widget(picker_paragraph, picker_area);
+    }
+
+    fn render_file_picker(&self, f: &mut Frame, file_picker: &FilePicker, area: Rect) {
+        self.render_picker(
+            f,
+            "Files",
+            &file_picker.query,
+            (file_picker.matches.len(), file_picker.selected),
+            |i| Line::from(file_picker.matches[i].to_string_lossy().into_owned()),
+            area,
+        );
+    }
+
+    fn render_recent_files(&self, f: &mut Frame, recent_files: &RecentFiles, area: Rect) {
+        let height = (recent_files.files.len() as u16 + 2).min(area.height);
+
+        let recent_area = Rect::new(area.x, area.bottom() - height, area.width, height);
+
+        let list_height = height.saturating_sub(2) as usize;
+
+        let skip = (recent_files.selected + 1).saturating_sub(list_height);
+
+        let lines: Vec<Line> = recent_files
+            .files
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .take(list_height)
+            .map(|(i, path)| {
+                let line = Line::from(path.to_string_lossy().into_owned());
+
+                if i == recent_files.selected {
+                    line.bg(self.palette.selection_bg)
                 } else {
-                    n -= 1;
+                    line
                 }
-            }
-        } else {
-            for i in 0..lines.len() {
-                line_numbers.push(i + editor.scroll_offset.row + 1);
-            }
-        }
+            })
+            .collect();
+
+        let recent_paragraph = Paragraph::new(lines)
+            .block(Block::bordered().title(" Recent files "))
+            .fg(self.palette.text_area_fg)
+            .bg(self.palette.text_area_bg);
+
+        f.render_widget(Clear, recent_area);
+
+        f.render_widget(recent_paragraph, recent_area);
+    }
+
+    fn render_command_palette(&self, f: &mut Frame, palette: &CommandPalette, area: Rect) {
+        self.render_picker(
+            f,
+            "Commands",
+            &palette.query,
+            (palette.matches.len(), palette.selected),
+            |i| {
+                let (name, description) = palette.entry(palette.matches[i]);
+
+                Line::from(vec![
+                    Span::raw(name.to_owned()),
+                    Span::raw(format!("  {}", description)).fg(self.palette.line_numbers_fg),
+                ])
+            },
+            area,
+        );
+    }
+
+    pub fn paint<T: TerminalBackend>(
+        &self,
+        terminal: &mut Terminal<T>,
+        editor: &Editor,
+        file_picker: Option<&FilePicker>,
+        command_palette: Option<&CommandPalette>,
+        completion: Option<&CompletionMenu>,
+        which_key: Option<&[(String, String)]>,
+    ) -> Result<()> {
+        let text_area = self.get_text_area();
+
+        let text_block = Block::default()
+            .fg(self.palette.text_area_fg)
+            .bg(self.palette.text_area_bg);
+
+        let line_numbers_area = self.get_line_numbers_area();
 
         let status_bar_area = self.get_status_bar_area();
 
@@ -177,51 +859,136 @@ impl Painter {
 
         let editor_mode_paragraph = Paragraph::new(editor.mode.to_string());
 
-        let file_name = match editor.document.path.as_ref() {
+        let file_name = match editor.document().path.as_ref() {
             Some(file_path) => file_path
                 .file_name()
                 .unwrap_or(file_path.as_os_str())
                 .to_string_lossy()
                 .to_string(),
 
-            None => "temp".to_owned(),
-        } + if editor.document.modified { " [+]" } else { "" };
+            None => "[No Name]".to_owned(),
+        } + if editor.document().readonly {
+            " [RO]"
+        } else {
+            ""
+        } + if editor.document().modified {
+            " [+]"
+        } else {
+            ""
+        } + &self
+            .buffer_position
+            .map(|(index, count)| format!(" [{}/{}]", index + 1, count))
+            .unwrap_or_default();
 
         let file_name_paragraph = Paragraph::new(file_name);
 
-        let position = format!("{}:{}", editor.position.row + 1, editor.position.column + 1);
+        let mut position = format!(
+            "{}  {}  {}:{}",
+            editor.document().encoding,
+            editor.config.filetype,
+            editor.position.row + 1,
+            editor.position.column + 1
+        );
+
+        if let Some((lines, size)) = editor.selection_size() {
+            let selection = match (&editor.mode, lines) {
+                (EditorMode::VisualBlock, _) => format!("{}x{}", lines, size),
+                (_, 1) => format!("{}C", size),
+                _ => format!("{}L {}C", lines, size),
+            };
+
+            position = format!("{}  {}", selection, position);
+        }
+
+        let errors = editor.diagnostic_count(DiagnosticSeverity::Error);
+
+        let warnings = editor.diagnostic_count(DiagnosticSeverity::Warning);
+
+        if errors > 0 || warnings > 0 {
+            position = format!("E:{} W:{}  {}", errors, warnings, position);
+        }
 
         let position_paragraph = Paragraph::new(position);
 
-        let editor_status_paragraph = Paragraph::new(editor.status.to_string());
+        let editor_status_paragraph = if editor.mode == EditorMode::Command {
+            Paragraph::new(format!(":{}", editor.command_line))
+        } else if editor.mode == EditorMode::Search {
+            let case = match editor.search_ignores_case(&editor.command_line) {
+                true => " [ic]",
+                false => "",
+            };
+
+            let paragraph = Paragraph::new(format!("/{}{}", editor.command_line, case));
+
+            match editor.search_failed {
+                true => paragraph.fg(self.palette.search_failed_fg),
+                false => paragraph,
+            }
+        } else {
+            match &editor.status {
+                // The main loop clears it at the deadline; this only covers a
+                // paint that lands after it.
+                EditorStatus::Message(_, Some(expiry)) if Instant::now() > *expiry => {
+                    Paragraph::new("")
+                }
+                status => Paragraph::new(status.to_string()),
+            }
+        };
+
+        let (cursor_y, cursor_x) = editor.visual_cursor(self.get_text_boundaries());
 
         terminal.draw(|f| {
-            f.set_cursor(
-                (editor
-                    .position
-                    .column
-                    .saturating_sub(editor.scroll_offset.column) as u16)
-                    .saturating_add(text_area.x),
-                editor.position.row.saturating_sub(editor.scroll_offset.row) as u16,
-            );
+            if matches!(editor.mode, EditorMode::Command | EditorMode::Search) {
+                f.set_cursor(
+                    status_bar_area[3]
+                        .x
+                        .saturating_add(editor.command_line.width() as u16 + 1),
+                    status_bar_area[3].y,
+                );
+            } else {
+                f.set_cursor(
+                    text_area.x.saturating_add(cursor_x as u16),
+                    text_area.y.saturating_add(cursor_y as u16),
+                );
+            }
+
+            f.render_widget(text_block, text_area.union(status_bar_area[3]));
+
+            if let (Some(split), Some((areas, separator))) = (editor.split, self.split_areas) {
+                let borders = if split.vertical {
+                    Borders::LEFT
+                } else {
+                    Borders::TOP
+                };
 
-            for (line_rect, line) in lines {
                 f.render_widget(
-                    Paragraph::new(line_numbers.remove(0).to_string()).centered(),
-                    Rect::new(
-                        line_numbers_area.x,
-                        line_rect.y,
-                        line_numbers_area.width,
-                        line_numbers_area.height,
-                    ),
+                    Block::new()
+                        .borders(borders)
+                        .fg(self.palette.line_numbers_fg)
+                        .bg(self.palette.text_area_bg),
+                    separator,
                 );
 
-                f.render_widget(Paragraph::new(line), line_rect);
+                f.render_widget(
+                    Block::new().bg(self.palette.text_area_bg),
+                    areas[0].union(areas[2]),
+                );
+
+                self.render_view(f, editor, split.position, split.scroll_offset, areas, false);
             }
 
-            f.render_widget(text_block, text_area.union(status_bar_area[3]));
+            self.render_view(
+                f,
+                editor,
+                editor.position,
+                editor.scroll_offset(),
+                [line_numbers_area, self.get_gutter_area(), text_area],
+                true,
+            );
 
-            f.render_widget(line_numbers_block, line_numbers_area);
+            if let (Some(diff), Some(diff_area)) = (&self.diff, self.diff_area) {
+                self.render_diff_pane(f, editor, diff, diff_area);
+            }
 
             f.render_widget(
                 status_bar_block,
@@ -237,8 +1004,92 @@ impl Painter {
             f.render_widget(position_paragraph.centered(), status_bar_area[2]);
 
             f.render_widget(editor_status_paragraph.left_aligned(), status_bar_area[3]);
+
+            if !editor.overlay.is_empty() {
+                let overlay_area = text_area.union(line_numbers_area);
+
+                let height = (editor.overlay.len() as u16 + 2).min(overlay_area.height);
+
+                let overlay_area = Rect::new(
+                    overlay_area.x,
+                    overlay_area.bottom() - height,
+                    overlay_area.width,
+                    height,
+                );
+
+                let skip = editor
+                    .overlay
+                    .len()
+                    .saturating_sub(height.saturating_sub(2) as usize);
+
+                let overlay_paragraph = Paragraph::new(
+                    editor.overlay[skip..]
+                        .iter()
+                        .map(|line| Line::from(line.as_str()))
+                        .collect::<Vec<_>>(),
+                )
+                .block(Block::bordered())
+                .fg(self.palette.text_area_fg)
+                .bg(self.palette.text_area_bg);
+
+                f.render_widget(Clear, overlay_area);
+
+                f.render_widget(overlay_paragraph, overlay_area);
+            }
+
+            if let Some(recent_files) = editor.recent_files.as_ref() {
+                self.render_recent_files(f, recent_files, text_area.union(line_numbers_area));
+            }
+
+            if let Some(hover) = editor.hover_popup.as_ref() {
+                self.render_hover(f, hover, text_area, cursor_y as u16);
+            }
+
+            if let Some(completion) = completion {
+                self.render_completion(f, completion, text_area, (cursor_y, cursor_x));
+            }
+
+            if let Some(which_key) = which_key {
+                self.render_which_key(f, which_key, text_area);
+            }
+
+            if let Some(file_picker) = file_picker {
+                self.render_file_picker(f, file_picker, text_area.union(line_numbers_area));
+            }
+
+            if let Some(command_palette) = command_palette {
+                self.render_command_palette(f, command_palette, text_area.union(line_numbers_area));
+            }
         })?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heights(height: u16) -> (u16, u16, u16) {
+        let painter = Painter::new(Boundaries::new(80, height));
+
+        let [_, text, left, middle, right, message, _] = painter.areas;
+
+        assert!(left.height == middle.height && middle.height == right.height);
+
+        (text.height, left.height, message.height)
+    }
+
+    #[test]
+    fn status_bar_and_message_line_are_one_row() {
+        assert_eq!(heights(24), (22, 1, 1));
+    }
+
+    #[test]
+    fn short_terminals_give_the_text_its_row_first() {
+        // The message line goes first, then the status bar; neither ever
+        // grows past one row.
+        assert_eq!(heights(2), (1, 1, 0));
+        assert_eq!(heights(1), (1, 0, 0));
+    }
+}
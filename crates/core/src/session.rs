@@ -0,0 +1,181 @@
+use anyhow::Result;
+
+use toml::{Table, Value};
+
+use wind_view::buffers::BufferList;
+use wind_view::config::Config;
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Bumped whenever the layout of `session.toml` changes; files with another
+/// version are ignored.
+const SESSION_VERSION: u32 = 1;
+
+pub struct SessionFile {
+    pub path: PathBuf,
+    pub row: usize,
+    pub column: usize,
+}
+
+pub struct Session {
+    pub version: u32,
+    pub files: Vec<SessionFile>,
+    pub current: usize,
+    pub search_history: Vec<String>,
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        Some(Config::data_dir()?.join("session.toml"))
+    }
+
+    pub fn from_buffers(buffers: &BufferList) -> Session {
+        let mut files = Vec::new();
+
+        let mut current = 0;
+
+        for (index, editor) in buffers.iter().enumerate() {
+            let Some(path) = editor.document().canonical_path() else {
+                continue;
+            };
+
+            if index == buffers.index() {
+                current = files.len();
+            }
+
+            files.push(SessionFile {
+                path: path.to_path_buf(),
+                row: editor.position.row,
+                column: editor.position.column,
+            });
+        }
+
+        if files.is_empty() {
+            if let Some(session) = Session::load() {
+                (files, current) = (session.files, session.current);
+            }
+        }
+
+        Session {
+            version: SESSION_VERSION,
+            files,
+            current,
+            search_history: buffers.current().search_history.iter().cloned().collect(),
+        }
+    }
+
+    pub fn load() -> Option<Session> {
+        let table: Table = fs::read_to_string(Session::path()?).ok()?.parse().ok()?;
+
+        let version = table.get("version")?.as_integer()?;
+
+        if version != SESSION_VERSION as i64 {
+            return None;
+        }
+
+        let files: Vec<SessionFile> = table
+            .get("files")?
+            .as_array()?
+            .iter()
+            .filter_map(|file| {
+                let file = file.as_table()?;
+
+                let position = |key: &str| {
+                    file.get(key)
+                        .and_then(Value::as_integer)
+                        .and_then(|value| usize::try_from(value).ok())
+                        .unwrap_or(0)
+                };
+
+                Some(SessionFile {
+                    path: PathBuf::from(file.get("path")?.as_str()?),
+                    row: position("row"),
+                    column: position("column"),
+                })
+            })
+            .filter(|file| file.path.is_file())
+            .collect();
+
+        let current = table
+            .get("current")
+            .and_then(Value::as_integer)
+            .and_then(|value| usize::try_from(value).ok())
+            .unwrap_or(0)
+            .min(files.len().saturating_sub(1));
+
+        let search_history: Vec<String> = table
+            .get("search_history")
+            .and_then(Value::as_array)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| Some(entry.as_str()?.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if files.is_empty() && search_history.is_empty() {
+            return None;
+        }
+
+        Some(Session {
+            version: SESSION_VERSION,
+            files,
+            current,
+            search_history,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = Session::path() else {
+            return Ok(());
+        };
+
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let mut table = Table::new();
+
+                table.insert(
+                    "path".to_owned(),
+                    Value::from(file.path.to_string_lossy().into_owned()),
+                );
+
+                table.insert("row".to_owned(), Value::from(file.row as i64));
+
+                table.insert("column".to_owned(), Value::from(file.column as i64));
+
+                Value::Table(table)
+            })
+            .collect::<Vec<_>>();
+
+        let mut table = Table::new();
+
+        table.insert("version".to_owned(), Value::from(self.version as i64));
+
+        table.insert("files".to_owned(), Value::Array(files));
+
+        table.insert("current".to_owned(), Value::from(self.current as i64));
+
+        table.insert(
+            "search_history".to_owned(),
+            Value::Array(
+                self.search_history
+                    .iter()
+                    .cloned()
+                    .map(Value::from)
+                    .collect(),
+            ),
+        );
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, table.to_string())?;
+
+        Ok(())
+    }
+}
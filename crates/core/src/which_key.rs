@@ -0,0 +1,177 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use wind_view::editor::EditorMode;
+
+/// Multi-key bindings per mode, written as key sequences where `^g` is
+/// Ctrl+G. Entries ending in a prefix describe the group it opens.
+pub const NORMAL_KEYMAP: &[(&str, &str)] = &[
+    ("g", "goto"),
+    ("g^g", "File statistics"),
+    ("g0", "Start of line"),
+    ("g$", "End of line"),
+    ("gj", "Down a screen line"),
+    ("gk", "Up a screen line"),
+    ("gd", "Go to definition"),
+    ("gc", "comment"),
+    ("gcc", "Toggle comment"),
+    ("]", "next"),
+    ("]d", "Next diagnostic"),
+    ("[", "previous"),
+    ("[d", "Previous diagnostic"),
+    ("z", "fold"),
+    ("za", "Toggle fold"),
+    ("zf", "Fold the indented block"),
+    ("zE", "Delete all folds"),
+    ("d", "delete"),
+    ("dd", "Delete line"),
+    ("dw", "Delete to next word"),
+    ("de", "Delete to end of word"),
+    ("d0", "Delete to line start"),
+    ("d$", "Delete to line end"),
+    ("dj", "Delete line and the one below"),
+    ("dk", "Delete line and the one above"),
+    ("^w", "window"),
+    ("^ws", "Split"),
+    ("^wv", "Split vertically"),
+    ("^ww", "Other window"),
+    ("^wh", "Window to the left"),
+    ("^wj", "Window below"),
+    ("^wk", "Window above"),
+    ("^wl", "Window to the right"),
+    ("^wo", "Close the other window"),
+    ("^wc", "Close this window"),
+    ("c", "change"),
+    ("cc", "Change line"),
+    ("cw", "Change word"),
+    ("ce", "Change to end of word"),
+    ("c0", "Change to line start"),
+    ("c$", "Change to line end"),
+    ("cj", "Change line and the one below"),
+    ("ck", "Change line and the one above"),
+];
+
+pub const VISUAL_KEYMAP: &[(&str, &str)] = &[
+    ("g", "goto"),
+    ("gc", "Toggle comment"),
+    ("g0", "Start of line"),
+    ("g$", "End of line"),
+    ("gj", "Down a screen line"),
+    ("gk", "Up a screen line"),
+    ("z", "fold"),
+    ("zf", "Fold the selection"),
+];
+
+/// A trie of key sequences; a `None` subtrie marks a complete binding.
+#[derive(Default)]
+pub struct WhichKey {
+    entries: Vec<(KeyEvent, String, Option<WhichKey>)>,
+}
+
+impl WhichKey {
+    pub fn for_mode(mode: &EditorMode) -> WhichKey {
+        let keymap = match mode {
+            EditorMode::Normal => NORMAL_KEYMAP,
+            EditorMode::Visual => VISUAL_KEYMAP,
+            _ => &[],
+        };
+
+        let mut which_key = WhichKey::default();
+
+        for (keys, description) in keymap {
+            which_key.bind(&parse_keys(keys), description);
+        }
+
+        which_key
+    }
+
+    pub fn bind(&mut self, keys: &[KeyEvent], description: &str) {
+        let Some((first, rest)) = keys.split_first() else {
+            return;
+        };
+
+        let index = match self
+            .entries
+            .iter()
+            .position(|(key, ..)| same_key(key, first))
+        {
+            Some(index) => index,
+
+            None => {
+                self.entries.push((*first, String::new(), None));
+
+                self.entries.len() - 1
+            }
+        };
+
+        let entry = &mut self.entries[index];
+
+        if rest.is_empty() {
+            entry.1 = description.to_owned();
+        } else {
+            entry
+                .2
+                .get_or_insert_with(WhichKey::default)
+                .bind(rest, description);
+        }
+    }
+
+    pub fn lookup(&self, keys: &[KeyEvent]) -> Option<&WhichKey> {
+        let Some((first, rest)) = keys.split_first() else {
+            return Some(self);
+        };
+
+        self.entries
+            .iter()
+            .find(|(key, ..)| same_key(key, first))
+            .and_then(|(_, _, subtrie)| subtrie.as_ref())
+            .and_then(|subtrie| subtrie.lookup(rest))
+    }
+
+    pub fn continuations(&self) -> Vec<(String, String)> {
+        self.entries
+            .iter()
+            .map(|(key, description, subtrie)| {
+                let description = match subtrie {
+                    Some(_) => format!("+{}", description),
+                    None => description.clone(),
+                };
+
+                (key_label(key), description)
+            })
+            .collect()
+    }
+}
+
+fn same_key(a: &KeyEvent, b: &KeyEvent) -> bool {
+    a.code == b.code && a.modifiers == b.modifiers
+}
+
+pub fn parse_keys(keys: &str) -> Vec<KeyEvent> {
+    let mut events = Vec::new();
+
+    let mut chars = keys.chars();
+
+    while let Some(ch) = chars.next() {
+        let event = match ch {
+            '^' => match chars.next() {
+                Some(ch) => KeyEvent::new(KeyCode::Char(ch), KeyModifiers::CONTROL),
+                None => KeyEvent::from(KeyCode::Char('^')),
+            },
+            ch => KeyEvent::from(KeyCode::Char(ch)),
+        };
+
+        events.push(event);
+    }
+
+    events
+}
+
+fn key_label(key: &KeyEvent) -> String {
+    match key.code {
+        KeyCode::Char(ch) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            format!("Ctrl+{}", ch)
+        }
+        KeyCode::Char(ch) => ch.to_string(),
+        code => format!("{:?}", code),
+    }
+}
@@ -9,3 +9,11 @@ impl Boundaries {
         Boundaries { width, height }
     }
 }
+
+/// Falls back to a standard 80x24 terminal so code running without a real
+/// terminal (e.g. tests) never works with a zero-sized area.
+impl Default for Boundaries {
+    fn default() -> Self {
+        Boundaries::new(80, 24)
+    }
+}
@@ -0,0 +1,66 @@
+use crate::editor::Editor;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BufferSwitch {
+    Next,
+    Previous,
+}
+
+pub struct BufferList {
+    editors: Vec<Editor>,
+    current: usize,
+}
+
+impl BufferList {
+    pub fn new(editor: Editor) -> BufferList {
+        BufferList {
+            editors: vec![editor],
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> &Editor {
+        &self.editors[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Editor {
+        &mut self.editors[self.current]
+    }
+
+    pub fn index(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.editors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.editors.is_empty()
+    }
+
+    pub fn push(&mut self, editor: Editor) {
+        self.editors.push(editor);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.current = index.min(self.editors.len() - 1);
+    }
+
+    pub fn switch(&mut self, switch: BufferSwitch) {
+        let len = self.editors.len();
+
+        self.current = match switch {
+            BufferSwitch::Next => (self.current + 1) % len,
+            BufferSwitch::Previous => (self.current + len - 1) % len,
+        };
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Editor> {
+        self.editors.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Editor> {
+        self.editors.iter_mut()
+    }
+}
@@ -0,0 +1,407 @@
+use crate::filetype::FileType;
+
+use anyhow::{bail, Context, Result};
+
+use toml::{Table, Value};
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
+}
+
+impl FromStr for CursorShape {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block" => Ok(CursorShape::Block),
+            "bar" => Ok(CursorShape::Bar),
+            "underline" => Ok(CursorShape::Underline),
+            _ => bail!("Unknown cursor shape: {}", s),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WrapIndent {
+    None,
+    SameIndent,
+    FirstNonWhitespace,
+}
+
+impl FromStr for WrapIndent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(WrapIndent::None),
+            "same_indent" => Ok(WrapIndent::SameIndent),
+            "first_non_whitespace" => Ok(WrapIndent::FirstNonWhitespace),
+            _ => bail!("Unknown wrap indent: {}", s),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AutoPairs {
+    pub enabled: bool,
+    pub pairs: Vec<(char, char)>,
+}
+
+impl Default for AutoPairs {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pairs: vec![('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')],
+        }
+    }
+}
+
+impl AutoPairs {
+    pub fn closing(&self, open: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|(o, _)| *o == open)
+            .map(|(_, close)| *close)
+    }
+
+    pub fn is_closing(&self, ch: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == ch)
+    }
+}
+
+#[derive(Clone)]
+pub struct EditorConfig {
+    pub trim_on_save: bool,
+    pub format_on_save: bool,
+    /// Whether saving ends the file with a line ending. Unset, a file keeps
+    /// what it had when opened and a new one gets one.
+    pub insert_final_newline: Option<bool>,
+    pub cursor_line: bool,
+    pub normal_cursor: CursorShape,
+    pub insert_cursor: CursorShape,
+    pub replace_cursor: CursorShape,
+    pub visual_cursor: CursorShape,
+    pub comment_string: Option<String>,
+    pub auto_pairs: AutoPairs,
+    pub side_scroll_off: usize,
+    pub wrap: bool,
+    pub wrap_indent: WrapIndent,
+    pub list: bool,
+    pub clipboard: bool,
+    pub message_timeout: u64,
+    pub filetype: FileType,
+    pub tab_width: usize,
+    pub expand_tabs: bool,
+    pub modeline_lines: usize,
+    pub detect_indent: bool,
+    pub ignore_case: bool,
+    pub smart_case: bool,
+    pub which_key_delay: u64,
+    pub scroll_acceleration: usize,
+    pub restore_position: bool,
+    pub lsp_servers: HashMap<String, String>,
+}
+
+impl Default for EditorConfig {
+    fn default() -> Self {
+        Self {
+            trim_on_save: false,
+            format_on_save: false,
+            insert_final_newline: None,
+            cursor_line: false,
+            normal_cursor: CursorShape::Block,
+            insert_cursor: CursorShape::Bar,
+            replace_cursor: CursorShape::Underline,
+            visual_cursor: CursorShape::Underline,
+            comment_string: None,
+            auto_pairs: AutoPairs::default(),
+            side_scroll_off: 0,
+            wrap: false,
+            wrap_indent: WrapIndent::None,
+            list: false,
+            clipboard: false,
+            message_timeout: 0,
+            filetype: FileType::Text,
+            tab_width: 4,
+            expand_tabs: true,
+            modeline_lines: 5,
+            detect_indent: true,
+            ignore_case: false,
+            smart_case: true,
+            which_key_delay: 500,
+            scroll_acceleration: 20,
+            restore_position: true,
+            lsp_servers: HashMap::from(
+                [
+                    ("rust", "rust-analyzer"),
+                    ("python", "pylsp"),
+                    ("go", "gopls"),
+                    ("c", "clangd"),
+                    ("cpp", "clangd"),
+                ]
+                .map(|(filetype, server)| (filetype.to_owned(), server.to_owned())),
+            ),
+        }
+    }
+}
+
+impl EditorConfig {
+    pub fn for_file_type(filetype: FileType) -> EditorConfig {
+        EditorConfig {
+            comment_string: filetype.comment_string().map(String::from),
+            filetype,
+            tab_width: filetype.tab_width(),
+            expand_tabs: filetype.expand_tabs(),
+            ..Default::default()
+        }
+    }
+
+    pub fn apply(&mut self, key: &str, value: &Value) -> Result<()> {
+        let boolean = || value.as_bool().context("expected true or false");
+
+        let number = || {
+            value
+                .as_integer()
+                .and_then(|n| usize::try_from(n).ok())
+                .context("expected a non-negative number")
+        };
+
+        let string = || value.as_str().context("expected a string");
+
+        match key {
+            "trim_on_save" => self.trim_on_save = boolean()?,
+            "format_on_save" => self.format_on_save = boolean()?,
+            "insert_final_newline" | "eol_at_eof" => self.insert_final_newline = Some(boolean()?),
+            "cursor_line" => self.cursor_line = boolean()?,
+            "clipboard" => self.clipboard = boolean()?,
+            "wrap" => self.wrap = boolean()?,
+            "list" => self.list = boolean()?,
+            "auto_pairs" => self.auto_pairs.enabled = boolean()?,
+            "detect_indent" => self.detect_indent = boolean()?,
+            "ignore_case" => self.ignore_case = boolean()?,
+            "smart_case" => self.smart_case = boolean()?,
+            "restore_position" => self.restore_position = boolean()?,
+            "expand_tabs" => self.expand_tabs = boolean()?,
+            "tab_width" => self.tab_width = number()?.max(1),
+            "side_scroll_off" => self.side_scroll_off = number()?,
+            "modeline_lines" => self.modeline_lines = number()?,
+            "which_key_delay" => self.which_key_delay = number()? as u64,
+            "scroll_acceleration" => self.scroll_acceleration = number()?.max(1),
+            "message_timeout" => self.message_timeout = number()? as u64,
+            "comment_string" => self.comment_string = Some(string()?.to_owned()),
+            "normal_cursor" => self.normal_cursor = string()?.parse()?,
+            "insert_cursor" => self.insert_cursor = string()?.parse()?,
+            "replace_cursor" => self.replace_cursor = string()?.parse()?,
+            "visual_cursor" => self.visual_cursor = string()?.parse()?,
+            "wrap_indent" => self.wrap_indent = string()?.parse()?,
+            _ => bail!("Unknown option: {}", key),
+        }
+
+        Ok(())
+    }
+
+    pub fn set(&mut self, option: &str) -> Result<()> {
+        if let Some((name, value)) = option.split_once('=') {
+            match name {
+                "normalcursor" => self.normal_cursor = value.parse()?,
+                "insertcursor" => self.insert_cursor = value.parse()?,
+                "replacecursor" => self.replace_cursor = value.parse()?,
+                "visualcursor" => self.visual_cursor = value.parse()?,
+                "wrapindent" => self.wrap_indent = value.parse()?,
+                "commentstring" => self.comment_string = Some(value.to_owned()),
+                "sidescrolloff" => self.side_scroll_off = value.parse()?,
+                "messagetimeout" => self.message_timeout = value.parse()?,
+                "modelines" => self.modeline_lines = value.parse()?,
+                "whichkeydelay" => self.which_key_delay = value.parse()?,
+                "scrollacceleration" => self.scroll_acceleration = value.parse::<usize>()?.max(1),
+                "tabwidth" => self.tab_width = value.parse::<usize>()?.max(1),
+                _ if name.starts_with("lsp.") => {
+                    self.lsp_servers
+                        .insert(name["lsp.".len()..].to_owned(), value.to_owned());
+                }
+                _ => bail!("Unknown option: {}", name),
+            }
+
+            return Ok(());
+        }
+
+        let (name, value) = match option.strip_prefix("no") {
+            Some(name) => (name, false),
+            None => (option, true),
+        };
+
+        match name {
+            "trimonsave" => self.trim_on_save = value,
+            "formatonsave" => self.format_on_save = value,
+            "finalnewline" | "eol" => self.insert_final_newline = Some(value),
+            "cursorline" => self.cursor_line = value,
+            "clipboard" => self.clipboard = value,
+            "autopairs" => self.auto_pairs.enabled = value,
+            "wrap" => self.wrap = value,
+            "list" => self.list = value,
+            "expandtab" => self.expand_tabs = value,
+            "detectindent" => self.detect_indent = value,
+            "ignorecase" | "ic" => self.ignore_case = value,
+            "smartcase" | "scs" => self.smart_case = value,
+            "restoreposition" => self.restore_position = value,
+            _ => bail!("Unknown option: {}", option),
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Config {
+    pub filetypes: HashMap<FileType, EditorConfig>,
+    /// The keys config.toml sets for each file type, which win over
+    /// settings guessed from the file.
+    pub set_keys: HashMap<FileType, HashSet<String>>,
+    pub warnings: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            filetypes: FileType::ALL
+                .into_iter()
+                .map(|filetype| (filetype, EditorConfig::for_file_type(filetype)))
+                .collect(),
+            set_keys: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("wind").join("config.toml"))
+    }
+
+    pub fn data_dir() -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))?;
+
+        Some(data_home.join("wind"))
+    }
+
+    /// Loads the config file, falling back to the defaults when it is missing.
+    /// Problems are collected in `warnings` rather than failing startup.
+    pub fn load() -> Config {
+        match Config::path() {
+            Some(path) => Config::load_from(&path, false),
+            None => Config::default(),
+        }
+    }
+
+    pub fn load_from(path: &Path, required: bool) -> Config {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text),
+
+            Err(err) if err.kind() == ErrorKind::NotFound && !required => Config::default(),
+
+            Err(err) => Config {
+                warnings: vec![format!("Could not read {}: {}", path.display(), err)],
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn parse(text: &str) -> Config {
+        let mut config = Config::default();
+
+        let table: Table = match text.parse() {
+            Ok(table) => table,
+
+            Err(err) => {
+                config
+                    .warnings
+                    .push(format!("Invalid config.toml: {}", err));
+
+                return config;
+            }
+        };
+
+        for (key, value) in &table {
+            if key == "filetype" {
+                continue;
+            }
+
+            for (filetype, editor_config) in config.filetypes.iter_mut() {
+                if let Err(err) = editor_config.apply(key, value) {
+                    config
+                        .warnings
+                        .push(format!("config.toml: {}: {}", key, err));
+
+                    break;
+                }
+
+                config
+                    .set_keys
+                    .entry(*filetype)
+                    .or_default()
+                    .insert(key.clone());
+            }
+        }
+
+        let sections = table.get("filetype").and_then(Value::as_table);
+
+        for (name, section) in sections.into_iter().flatten() {
+            let (Some(filetype), Some(section)) = (FileType::from_name(name), section.as_table())
+            else {
+                config
+                    .warnings
+                    .push(format!("config.toml: unknown file type {}", name));
+
+                continue;
+            };
+
+            let editor_config = config.filetypes.get_mut(&filetype).unwrap();
+
+            for (key, value) in section {
+                match editor_config.apply(key, value) {
+                    Ok(()) => {
+                        config
+                            .set_keys
+                            .entry(filetype)
+                            .or_default()
+                            .insert(key.clone());
+                    }
+
+                    Err(err) => config
+                        .warnings
+                        .push(format!("config.toml: filetype.{}.{}: {}", name, key, err)),
+                }
+            }
+        }
+
+        config
+    }
+
+    pub fn is_set(&self, filetype: FileType, key: &str) -> bool {
+        self.set_keys
+            .get(&filetype)
+            .is_some_and(|keys| keys.contains(key))
+    }
+
+    pub fn editor_config(&self, filetype: FileType) -> EditorConfig {
+        self.filetypes
+            .get(&filetype)
+            .cloned()
+            .unwrap_or_else(|| EditorConfig::for_file_type(filetype))
+    }
+}
@@ -0,0 +1,34 @@
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl DiagnosticSeverity {
+    pub fn from_lsp(severity: Option<u64>) -> DiagnosticSeverity {
+        match severity {
+            Some(2) => DiagnosticSeverity::Warning,
+            Some(3) => DiagnosticSeverity::Information,
+            Some(4) => DiagnosticSeverity::Hint,
+            _ => DiagnosticSeverity::Error,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            DiagnosticSeverity::Error => "E",
+            DiagnosticSeverity::Warning => "W",
+            DiagnosticSeverity::Information | DiagnosticSeverity::Hint => "I",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Diagnostic {
+    pub row: usize,
+    pub col: usize,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
@@ -1,14 +1,106 @@
+use crate::config::Config;
+use crate::filetype::{detect_file_type, FileType};
+use crate::history::{Change, History};
+use crate::modeline::Modeline;
 use crate::position::Position;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+
+use regex::{Regex, RegexBuilder};
 
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
 
-#[derive(Default)]
+fn disk_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[derive(Debug)]
+pub enum DocumentError {
+    RowOutOfBounds(usize),
+    ChangedOnDisk,
+    IsDirectory(PathBuf),
+    Unencodable(Encoding),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentError::RowOutOfBounds(row) => write!(f, "Row {} is out of bounds", row + 1),
+            DocumentError::ChangedOnDisk => {
+                write!(f, "File changed on disk (use :w! to override)")
+            }
+            DocumentError::IsDirectory(path) => write!(
+                f,
+                "{} is a directory (use Ctrl+P to pick a file)",
+                path.display()
+            ),
+            DocumentError::Unencodable(encoding) => write!(
+                f,
+                "Text can't be written as {} (use :set encoding=utf-8)",
+                encoding
+            ),
+        }
+    }
+}
+
+impl Error for DocumentError {}
+
+#[derive(PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(grapheme: &str) -> CharClass {
+        match grapheme.chars().next() {
+            Some(c) if c.is_whitespace() => CharClass::Whitespace,
+            Some(c) if c.is_alphanumeric() || c == '_' => CharClass::Word,
+            _ => CharClass::Punctuation,
+        }
+    }
+}
+
+pub fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    match grapheme {
+        "\t" => {
+            let tab_width = tab_width.max(1);
+
+            tab_width - column % tab_width
+        }
+        _ => grapheme.width(),
+    }
+}
+
+fn unnamed_swap_dir() -> PathBuf {
+    Config::data_dir().map_or_else(env::temp_dir, |dir| dir.join("swap"))
+}
+
+/// The start of the swap file names of unnamed buffers opened in the working
+/// directory, which is spelled out so that a restarted wind finds them.
+fn unnamed_swap_prefix() -> String {
+    let dir = env::current_dir().unwrap_or_default();
+
+    format!("{}.", dir.to_string_lossy().replace(['/', '\\', ':'], "%"))
+}
+
+#[derive(Default, Clone)]
 pub struct Row {
     pub content: String,
     len: usize,
@@ -29,7 +121,7 @@ impl From<String> for Row {
 
 impl Row {
     pub fn render(&self, start: usize, end: usize) -> String {
-        let end = end.min(self.content.len());
+        let end = end.min(self.len());
         let start = start.min(end);
 
         self.content
@@ -39,6 +131,28 @@ impl Row {
             .collect()
     }
 
+    pub fn byte_index(&self, col: usize) -> usize {
+        self.content
+            .grapheme_indices(true)
+            .nth(col)
+            .map_or(self.content.len(), |(i, _)| i)
+    }
+
+    pub fn column_at_byte(&self, index: usize) -> usize {
+        self.content
+            .grapheme_indices(true)
+            .take_while(|(i, _)| *i < index)
+            .count()
+    }
+
+    pub fn insert_str(&mut self, at: usize, text: &str) {
+        let index = self.byte_index(at);
+
+        self.content.insert_str(index, text);
+
+        self.update_len();
+    }
+
     pub fn split(&mut self, at: usize) -> Row {
         let start = self.content.graphemes(true).take(at).collect();
 
@@ -50,11 +164,289 @@ impl Row {
         Row::from(mid)
     }
 
+    pub fn find_number_at_or_after(&self, col: usize) -> Option<(Range<usize>, i64)> {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let is_digit = |i: usize, radix: u32| {
+            graphemes
+                .get(i)
+                .and_then(|g| g.chars().next())
+                .is_some_and(|c| c.is_digit(radix))
+        };
+
+        let mut i = 0;
+
+        while i < graphemes.len() {
+            let is_hex = graphemes[i] == "0"
+                && matches!(graphemes.get(i + 1), Some(&"x" | &"X"))
+                && is_digit(i + 2, 16);
+
+            if !is_hex && !is_digit(i, 10) {
+                i += 1;
+
+                continue;
+            }
+
+            let start = if !is_hex && i > 0 && graphemes[i - 1] == "-" {
+                i - 1
+            } else {
+                i
+            };
+
+            let (radix, digits_start) = if is_hex { (16, i + 2) } else { (10, i) };
+
+            let mut end = digits_start;
+
+            while is_digit(end, radix) {
+                end += 1;
+            }
+
+            if end > col {
+                let digits: String = graphemes[digits_start..end].concat();
+
+                let value = i64::from_str_radix(&digits, radix).ok()?;
+
+                let value = if start < i { -value } else { value };
+
+                return Some((start..end, value));
+            }
+
+            i = end;
+        }
+
+        None
+    }
+
+    pub fn wrap(&self, width: usize, indent: usize) -> Vec<usize> {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let mut starts = vec![0];
+
+        if width == 0 {
+            return starts;
+        }
+
+        let mut start = 0;
+
+        let mut line_width = width;
+
+        while graphemes.len() - start > line_width {
+            let limit = start + line_width;
+
+            let break_at = (start + 1..=limit)
+                .rev()
+                .find(|&i| CharClass::of(graphemes[i - 1]) == CharClass::Whitespace)
+                .unwrap_or(limit);
+
+            starts.push(break_at);
+
+            start = break_at;
+
+            line_width = width.saturating_sub(indent).max(1);
+        }
+
+        starts
+    }
+
+    pub fn leading_whitespace(&self) -> &str {
+        let trimmed = self.content.trim_start();
+
+        &self.content[..self.content.len() - trimmed.len()]
+    }
+
+    pub fn prev_word_start(&self, col: usize) -> usize {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let mut i = col.min(graphemes.len());
+
+        while i > 0 && CharClass::of(graphemes[i - 1]) == CharClass::Whitespace {
+            i -= 1;
+        }
+
+        if i > 0 {
+            let class = CharClass::of(graphemes[i - 1]);
+
+            while i > 0 && CharClass::of(graphemes[i - 1]) == class {
+                i -= 1;
+            }
+        }
+
+        i
+    }
+
+    pub fn partial_word_start(&self, col: usize) -> usize {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let mut i = col.min(graphemes.len());
+
+        while i > 0 && CharClass::of(graphemes[i - 1]) == CharClass::Word {
+            i -= 1;
+        }
+
+        i
+    }
+
+    pub fn next_word_start(&self, col: usize) -> usize {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let mut i = col;
+
+        if i >= graphemes.len() {
+            return graphemes.len();
+        }
+
+        let class = CharClass::of(graphemes[i]);
+
+        if class != CharClass::Whitespace {
+            while i < graphemes.len() && CharClass::of(graphemes[i]) == class {
+                i += 1;
+            }
+        }
+
+        while i < graphemes.len() && CharClass::of(graphemes[i]) == CharClass::Whitespace {
+            i += 1;
+        }
+
+        i
+    }
+
+    pub fn word_end(&self, col: usize) -> usize {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let mut i = col + 1;
+
+        while i < graphemes.len() && CharClass::of(graphemes[i]) == CharClass::Whitespace {
+            i += 1;
+        }
+
+        if i >= graphemes.len() {
+            return graphemes.len().saturating_sub(1);
+        }
+
+        let class = CharClass::of(graphemes[i]);
+
+        while i + 1 < graphemes.len() && CharClass::of(graphemes[i + 1]) == class {
+            i += 1;
+        }
+
+        i
+    }
+
+    pub fn current_word_end(&self, col: usize) -> usize {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let mut i = col;
+
+        if let Some(class) = graphemes.get(i).map(|g| CharClass::of(g)) {
+            while i < graphemes.len() && CharClass::of(graphemes[i]) == class {
+                i += 1;
+            }
+        }
+
+        i
+    }
+
+    pub fn word_bounds(&self, col: usize) -> (usize, usize) {
+        let graphemes: Vec<&str> = self.content.graphemes(true).collect();
+
+        let Some(class) = graphemes.get(col).map(|g| CharClass::of(g)) else {
+            return (col, col);
+        };
+
+        let mut start = col;
+
+        while start > 0 && CharClass::of(graphemes[start - 1]) == class {
+            start -= 1;
+        }
+
+        (start, self.current_word_end(col))
+    }
+
+    pub fn grapheme_col_from_utf16_offset(&self, utf16_offset: usize) -> usize {
+        let mut units = 0;
+
+        for (i, grapheme) in self.content.graphemes(true).enumerate() {
+            units += grapheme.encode_utf16().count();
+
+            if units > utf16_offset {
+                return i;
+            }
+        }
+
+        self.len()
+    }
+
+    pub fn utf16_offset_from_grapheme_col(&self, col: usize) -> usize {
+        self.content
+            .graphemes(true)
+            .take(col)
+            .map(|g| g.encode_utf16().count())
+            .sum()
+    }
+
+    pub fn grapheme_at(&self, index: usize) -> Option<&str> {
+        self.content.graphemes(true).nth(index)
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.len
     }
 
+    pub fn width(&self, tab_width: usize) -> usize {
+        self.column_to_display(self.len, tab_width)
+    }
+
+    pub fn column_to_display(&self, col: usize, tab_width: usize) -> usize {
+        let width = self
+            .content
+            .graphemes(true)
+            .take(col)
+            .fold(0, |width, grapheme| {
+                width + grapheme_width(grapheme, width, tab_width)
+            });
+
+        width + col.saturating_sub(self.len)
+    }
+
+    pub fn display_to_column(&self, display: usize, tab_width: usize) -> usize {
+        let mut width = 0;
+
+        for (col, grapheme) in self.content.graphemes(true).enumerate() {
+            width += grapheme_width(grapheme, width, tab_width);
+
+            if width > display {
+                return col;
+            }
+        }
+
+        self.len
+    }
+
+    pub fn render_expanded(&self, start: usize, end: usize, tab_width: usize) -> String {
+        let mut width = self.column_to_display(start, tab_width);
+
+        let mut text = String::new();
+
+        for grapheme in self
+            .content
+            .graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+        {
+            let cells = grapheme_width(grapheme, width, tab_width);
+
+            match grapheme {
+                "\t" => text.push_str(&" ".repeat(cells)),
+                _ => text.push_str(grapheme),
+            }
+
+            width += cells;
+        }
+
+        text
+    }
+
     #[inline]
     pub fn update_len(&mut self) {
         self.len = self.content.graphemes(true).count();
@@ -66,109 +458,1382 @@ impl Row {
     }
 }
 
-#[derive(Default)]
-pub struct Document {
-    pub path: Option<PathBuf>,
-    pub rows: Vec<Row>,
-    pub modified: bool,
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
 }
 
-impl Document {
-    pub fn open(file_path: Option<PathBuf>) -> Result<Document> {
-        let mut rows = Vec::new();
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Indent {
+    pub expand_tabs: bool,
+    pub width: Option<usize>,
+}
 
-        if file_path.as_ref().is_some_and(|f| f.exists()) {
-            let file = File::open(file_path.as_ref().unwrap())?;
-            let reader = BufReader::new(file);
+/// How the file's bytes map to text. Files that aren't valid UTF-8 or
+/// UTF-16 are read as Latin-1, where every byte is a character.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+pub struct Decoded<'a> {
+    pub text: Cow<'a, str>,
+    pub encoding: Encoding,
+    pub has_bom: bool,
+    pub lossy: bool,
+}
+
+impl Encoding {
+    /// Decodes `bytes`, trusting a BOM first, then guessing UTF-16 from the
+    /// zero bytes of ASCII text, then trying UTF-8 and falling back to
+    /// Latin-1.
+    pub fn decode(bytes: &[u8]) -> Decoded<'_> {
+        let bom = encoding_rs::Encoding::for_bom(bytes);
 
-            for line in reader.lines() {
-                rows.push(Row::from(line?));
+        let (encoding, bom_len) = match bom {
+            Some((encoding, bom_len)) if encoding == encoding_rs::UTF_8 => {
+                (Encoding::Utf8, bom_len)
             }
-        } else {
-            rows.push(Row::default());
-        }
+            Some((encoding, bom_len)) if encoding == encoding_rs::UTF_16LE => {
+                (Encoding::Utf16Le, bom_len)
+            }
+            Some((_, bom_len)) => (Encoding::Utf16Be, bom_len),
+            None => match Encoding::guess_utf16(bytes) {
+                Some(encoding) => (encoding, 0),
+                None => {
+                    let (text, encoding) = match encoding_rs::UTF_8
+                        .decode_without_bom_handling_and_without_replacement(bytes)
+                    {
+                        Some(text) => (text, Encoding::Utf8),
+                        None => (encoding_rs::mem::decode_latin1(bytes), Encoding::Latin1),
+                    };
 
-        Ok(Document {
-            path: file_path,
-            rows,
-            modified: false,
-        })
+                    return Decoded {
+                        text,
+                        encoding,
+                        has_bom: false,
+                        lossy: false,
+                    };
+                }
+            },
+        };
+
+        let (text, lossy) = encoding
+            .codec()
+            .decode_without_bom_handling(&bytes[bom_len..]);
+
+        Decoded {
+            text,
+            encoding,
+            has_bom: bom_len > 0,
+            lossy,
+        }
     }
 
-    pub fn insert_new_line(&mut self, at: Position) {
-        self.modified = true;
+    pub fn decode_as(self, bytes: &[u8]) -> Decoded<'_> {
+        if self == Encoding::Latin1 {
+            return Decoded {
+                text: encoding_rs::mem::decode_latin1(bytes),
+                encoding: self,
+                has_bom: false,
+                lossy: false,
+            };
+        }
 
-        let row = self.rows.get_mut(at.row).unwrap();
+        let bom_len = match encoding_rs::Encoding::for_bom(bytes) {
+            Some((codec, bom_len)) if codec == self.codec() => bom_len,
+            _ => 0,
+        };
 
-        let new_row = row.split(at.column);
+        let (text, lossy) = self.codec().decode_without_bom_handling(&bytes[bom_len..]);
 
-        self.rows.insert(at.row.saturating_add(1), new_row);
+        Decoded {
+            text,
+            encoding: self,
+            has_bom: bom_len > 0,
+            lossy,
+        }
     }
 
-    pub fn insert(&mut self, at: Position, ch: char) {
-        self.modified = true;
+    fn guess_utf16(bytes: &[u8]) -> Option<Encoding> {
+        let sample = &bytes[..bytes.len().min(1024) & !1];
 
-        if ch == '\n' {
-            self.insert_new_line(at);
+        let pairs = sample.len() / 2;
 
-            return;
+        if pairs == 0 {
+            return None;
         }
 
-        let row = self.rows.get_mut(at.row).unwrap();
+        let zeros = |offset: usize| {
+            sample
+                .iter()
+                .skip(offset)
+                .step_by(2)
+                .filter(|byte| **byte == 0)
+                .count()
+        };
 
-        row.content.insert(at.column, ch);
+        let (even, odd) = (zeros(0), zeros(1));
 
-        row.update_len();
+        if odd * 2 > pairs && even * 10 < pairs {
+            Some(Encoding::Utf16Le)
+        } else if even * 2 > pairs && odd * 10 < pairs {
+            Some(Encoding::Utf16Be)
+        } else {
+            None
+        }
     }
 
-    pub fn delete(&mut self, at: Position) {
-        self.modified = true;
+    /// The encoding_rs codec, used to decode UTF-8 with a BOM and UTF-16.
+    /// Latin-1 is decoded with `mem::decode_latin1` instead, as the WHATWG
+    /// label maps it to windows-1252.
+    fn codec(&self) -> &'static encoding_rs::Encoding {
+        match self {
+            Encoding::Utf8 => encoding_rs::UTF_8,
+            Encoding::Utf16Le => encoding_rs::UTF_16LE,
+            Encoding::Utf16Be => encoding_rs::UTF_16BE,
+            Encoding::Latin1 => encoding_rs::WINDOWS_1252,
+        }
+    }
 
-        if at.column == self.row_len(at.row) && at.row < self.rows.len() - 1 {
-            let next_row = self.rows.remove(at.row.saturating_add(1));
+    pub fn encode<'a>(&self, text: &'a str) -> Option<Cow<'a, [u8]>> {
+        match self {
+            Encoding::Utf8 => Some(Cow::Borrowed(text.as_bytes())),
+            // encoding_rs only decodes UTF-16, so encode by hand.
+            Encoding::Utf16Le => Some(Cow::Owned(
+                text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            )),
+            Encoding::Utf16Be => Some(Cow::Owned(
+                text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            )),
+            Encoding::Latin1 => text
+                .chars()
+                .all(|ch| (ch as u32) <= 0xff)
+                .then(|| encoding_rs::mem::encode_latin1_lossy(text)),
+        }
+    }
+}
 
-            let row = self.rows.get_mut(at.row).unwrap();
+impl FromStr for Encoding {
+    type Err = anyhow::Error;
 
-            let result = row
-                .content
-                .graphemes(true)
-                .chain(next_row.content.graphemes(true))
-                .collect();
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            _ => bail!("Unknown encoding: {}", s),
+        }
+    }
+}
 
-            row.content = result;
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Utf8 => write!(f, "UTF-8"),
+            Encoding::Utf16Le => write!(f, "UTF-16LE"),
+            Encoding::Utf16Be => write!(f, "UTF-16BE"),
+            Encoding::Latin1 => write!(f, "ISO-8859-1"),
+        }
+    }
+}
 
-            row.update_len();
-        } else {
-            let row = self.rows.get_mut(at.row).unwrap();
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchOptions {
+    pub ignore_case: bool,
+}
+
+#[derive(Default)]
+pub struct Document {
+    pub path: Option<PathBuf>,
+    canonical_path: Option<PathBuf>,
+    disk_stamp: Option<(SystemTime, u64)>,
+    pub rows: Vec<Row>,
+    pub modified: bool,
+    pub readonly: bool,
+    /// Refuses every edit, unlike `readonly` which only guards saving.
+    pub scratch: bool,
+    pub has_bom: bool,
+    pub encoding: Encoding,
+    pub lossy: bool,
+    pub final_newline: bool,
+    pub line_ending: LineEnding,
+    pub history: History,
+    pub swap_pending: bool,
+    pub unnamed_swap: usize,
+    pub edit_refused: bool,
+    pub revision: u64,
+}
+
+impl Document {
+    pub fn open(file_path: Option<PathBuf>) -> Result<Document> {
+        let mut rows = Vec::new();
+
+        let mut has_bom = false;
 
-            let mut result: String = row.content.graphemes(true).collect();
+        let mut encoding = Encoding::default();
 
-            result.remove(at.column);
+        let mut lossy = false;
 
-            row.content = result;
+        // A new file gets a final line ending.
+        let mut final_newline = true;
 
-            row.update_len();
+        if let Some(path) = file_path.as_ref().filter(|path| path.is_dir()) {
+            return Err(DocumentError::IsDirectory(path.clone()).into());
         }
-    }
 
-    pub fn save(&mut self) -> Result<usize> {
-        let file = File::create(self.path.as_ref().unwrap())?;
+        let canonical_path = file_path
+            .as_ref()
+            .and_then(|path| fs::canonicalize(path).ok());
 
-        let mut writer = BufWriter::new(file);
+        if let Some(canonical_path) = canonical_path.as_ref() {
+            let bytes = fs::read(canonical_path)?;
 
-        let mut n = 0;
+            let decoded = Encoding::decode(&bytes);
 
-        for row in &self.rows {
-            n += writer.write(row.content.as_bytes())?;
-            n += writer.write(b"\n")?;
+            (encoding, has_bom, lossy) = (decoded.encoding, decoded.has_bom, decoded.lossy);
+
+            final_newline = decoded.text.ends_with('\n');
+
+            // `lines` drops the newline ending the last line, so a file that
+            // ends with one doesn't get an extra empty row.
+            rows = decoded
+                .text
+                .lines()
+                .map(|line| Row::from(line.to_owned()))
+                .collect();
         }
 
-        self.modified = false;
+        if rows.is_empty() {
+            rows.push(Row::default());
+        }
 
-        Ok(n)
-    }
+        let disk_stamp = canonical_path.as_deref().and_then(disk_stamp);
 
-    #[inline]
+        Ok(Document {
+            path: file_path,
+            canonical_path,
+            disk_stamp,
+            rows,
+            modified: false,
+            readonly: false,
+            scratch: false,
+            has_bom,
+            encoding,
+            lossy,
+            final_newline,
+            line_ending: LineEnding::default(),
+            history: History::default(),
+            swap_pending: false,
+            unnamed_swap: 0,
+            edit_refused: false,
+            revision: 0,
+        })
+    }
+
+    pub fn from_stdin() -> Result<Document> {
+        let mut bytes = Vec::new();
+
+        io::stdin().read_to_end(&mut bytes)?;
+
+        let decoded = Encoding::decode(&bytes);
+
+        let mut document = Document::open(None)?;
+
+        document.rows = decoded
+            .text
+            .lines()
+            .map(|line| Row::from(line.to_owned()))
+            .collect();
+
+        if document.rows.is_empty() {
+            document.rows.push(Row::default());
+        }
+
+        document.encoding = decoded.encoding;
+
+        document.has_bom = decoded.has_bom;
+
+        document.lossy = decoded.lossy;
+
+        document.final_newline = decoded.text.ends_with('\n');
+
+        Ok(document)
+    }
+
+    pub fn reload_as(&mut self, encoding: Encoding) -> Result<()> {
+        let Some(path) = self.canonical_path.as_deref() else {
+            return Ok(());
+        };
+
+        let bytes = fs::read(path)?;
+
+        let decoded = encoding.decode_as(&bytes);
+
+        self.rows = decoded
+            .text
+            .lines()
+            .map(|line| Row::from(line.to_owned()))
+            .collect();
+
+        if self.rows.is_empty() {
+            self.rows.push(Row::default());
+        }
+
+        self.encoding = decoded.encoding;
+
+        self.has_bom = decoded.has_bom;
+
+        self.lossy = decoded.lossy;
+
+        self.final_newline = decoded.text.ends_with('\n');
+
+        Ok(())
+    }
+
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.path = Some(path);
+
+        self.canonical_path = None;
+
+        self.disk_stamp = None;
+    }
+
+    fn replace_rows(&mut self, at: usize, count: usize, rows: Vec<String>) {
+        if self.scratch {
+            self.edit_refused = true;
+
+            return;
+        }
+
+        self.modified = true;
+
+        self.swap_pending = true;
+
+        self.revision += 1;
+
+        let before = self.rows[at..at + count]
+            .iter()
+            .map(|r| r.content.clone())
+            .collect();
+
+        self.history.record(Change {
+            row: at,
+            before,
+            after: rows.clone(),
+        });
+
+        self.rows
+            .splice(at..at + count, rows.into_iter().map(Row::from));
+    }
+
+    pub fn insert_row(&mut self, at: usize) {
+        self.replace_rows(at, 0, vec![String::new()]);
+    }
+
+    pub fn insert_new_line(&mut self, at: Position) -> Result<()> {
+        let mut row = self
+            .rows
+            .get(at.row)
+            .ok_or(DocumentError::RowOutOfBounds(at.row))?
+            .clone();
+
+        let new_row = row.split(at.column);
+
+        self.replace_rows(at.row, 1, vec![row.content, new_row.content]);
+
+        Ok(())
+    }
+
+    pub fn insert(&mut self, at: Position, ch: char) -> Result<()> {
+        if ch == '\n' {
+            return self.insert_new_line(at);
+        }
+
+        let mut row = self
+            .rows
+            .get(at.row)
+            .ok_or(DocumentError::RowOutOfBounds(at.row))?
+            .clone();
+
+        row.insert_str(at.column, ch.encode_utf8(&mut [0; 4]));
+
+        self.replace_rows(at.row, 1, vec![row.content]);
+
+        Ok(())
+    }
+
+    pub fn replace_grapheme(&mut self, at: Position, with: &str) -> Result<Option<String>> {
+        let row = self
+            .rows
+            .get(at.row)
+            .ok_or(DocumentError::RowOutOfBounds(at.row))?;
+
+        let replaced = row.grapheme_at(at.column).map(str::to_owned);
+
+        let mut result: String = row.content.graphemes(true).take(at.column).collect();
+
+        result.push_str(with);
+
+        result.extend(row.content.graphemes(true).skip(at.column + 1));
+
+        self.replace_rows(at.row, 1, vec![result]);
+
+        Ok(replaced)
+    }
+
+    pub fn insert_str(&mut self, at: Position, text: &str) -> Result<Position> {
+        let mut row = self
+            .rows
+            .get(at.row)
+            .ok_or(DocumentError::RowOutOfBounds(at.row))?
+            .clone();
+
+        let Some((first, rest)) = text.split_once('\n') else {
+            row.insert_str(at.column, text);
+
+            let end = Position {
+                column: at.column + row.len() - self.rows[at.row].len(),
+                ..at
+            };
+
+            self.replace_rows(at.row, 1, vec![row.content]);
+
+            return Ok(end);
+        };
+
+        let mut tail = row.split(at.column);
+
+        row.insert_str(row.len(), first);
+
+        let mut lines = vec![row.content];
+
+        lines.extend(rest.split('\n').map(String::from));
+
+        let last = lines.len() - 1;
+
+        let end = Position {
+            row: at.row + last,
+            column: lines[last].graphemes(true).count(),
+            ..at
+        };
+
+        tail.insert_str(0, &lines[last]);
+
+        lines[last] = tail.content;
+
+        self.replace_rows(at.row, 1, lines);
+
+        Ok(end)
+    }
+
+    pub fn delete(&mut self, at: Position) {
+        let is_last_row = at.row.saturating_add(1) >= self.rows.len();
+
+        if is_last_row && at.column >= self.row_len(at.row) {
+            return;
+        }
+
+        if at.column == self.row_len(at.row) && !is_last_row {
+            let result = self.rows[at.row]
+                .content
+                .graphemes(true)
+                .chain(self.rows[at.row + 1].content.graphemes(true))
+                .collect();
+
+            self.replace_rows(at.row, 2, vec![result]);
+        } else {
+            let row = self.rows.get(at.row).unwrap();
+
+            let result = row
+                .content
+                .graphemes(true)
+                .enumerate()
+                .filter(|(i, _)| *i != at.column)
+                .map(|(_, g)| g)
+                .collect();
+
+            self.replace_rows(at.row, 1, vec![result]);
+        }
+    }
+
+    pub fn delete_range(&mut self, start: Position, end: Position) -> String {
+        let (start, end) = if (end.row, end.column) < (start.row, start.column) {
+            (end, start)
+        } else {
+            (start, end)
+        };
+
+        let Some(last_row) = self.rows.len().checked_sub(1) else {
+            return String::new();
+        };
+
+        if start.row > last_row {
+            return String::new();
+        }
+
+        let (end_row, end_column) = if end.row > last_row {
+            (last_row, usize::MAX)
+        } else {
+            (end.row, end.column)
+        };
+
+        let deleted = self.text_range(start, end);
+
+        if deleted.is_empty() {
+            return deleted;
+        }
+
+        let first = &self.rows[start.row];
+
+        let last = &self.rows[end_row];
+
+        let merged = first.render(0, start.column) + &last.render(end_column, last.len());
+
+        self.replace_rows(start.row, end_row - start.row + 1, vec![merged]);
+
+        deleted
+    }
+
+    pub fn delete_to_line_start(&mut self, at: Position) -> String {
+        let Some(row) = self.rows.get(at.row) else {
+            return String::new();
+        };
+
+        let deleted = row.render(0, at.column);
+
+        if !deleted.is_empty() {
+            let result = row.render(at.column, row.len());
+
+            self.replace_rows(at.row, 1, vec![result]);
+        }
+
+        deleted
+    }
+
+    pub fn trim_trailing_whitespace(&mut self) {
+        self.history.begin_group();
+
+        for i in 0..self.rows.len() {
+            let content = &self.rows[i].content;
+
+            let trimmed = content.trim_end_matches([' ', '\t']);
+
+            if trimmed.len() != content.len() {
+                let trimmed = trimmed.to_owned();
+
+                self.replace_rows(i, 1, vec![trimmed]);
+            }
+        }
+
+        self.history.end_group();
+    }
+
+    pub fn retab(&mut self, start_row: usize, end_row: usize, tab_width: usize, to_tabs: bool) {
+        let tab_width = tab_width.max(1);
+
+        self.history.begin_group();
+
+        for i in start_row..=end_row.min(self.rows.len().saturating_sub(1)) {
+            let content = &self.rows[i].content;
+
+            let indent_len = content.len() - content.trim_start_matches([' ', '\t']).len();
+
+            let (indent, rest) = content.split_at(indent_len);
+
+            let width = indent.chars().fold(0, |width, ch| match ch {
+                '\t' => (width / tab_width + 1) * tab_width,
+                _ => width + 1,
+            });
+
+            let new_indent = if to_tabs {
+                "\t".repeat(width / tab_width) + &" ".repeat(width % tab_width)
+            } else {
+                " ".repeat(width)
+            };
+
+            if new_indent != indent {
+                let row = new_indent + rest;
+
+                self.replace_rows(i, 1, vec![row]);
+            }
+        }
+
+        self.history.end_group();
+    }
+
+    pub fn substitute(
+        &mut self,
+        start_row: usize,
+        end_row: usize,
+        regex: &Regex,
+        replacement: &str,
+        global: bool,
+    ) -> (usize, usize) {
+        let limit = if global { 0 } else { 1 };
+
+        let (mut replacements, mut rows) = (0, 0);
+
+        self.history.begin_group();
+
+        for i in start_row..=end_row.min(self.rows.len().saturating_sub(1)) {
+            let content = &self.rows[i].content;
+
+            let count = match global {
+                true => regex.find_iter(content).count(),
+                false => regex.is_match(content) as usize,
+            };
+
+            if count == 0 {
+                continue;
+            }
+
+            let row = regex.replacen(content, limit, replacement).into_owned();
+
+            replacements += count;
+
+            rows += 1;
+
+            self.replace_rows(i, 1, vec![row]);
+        }
+
+        self.history.end_group();
+
+        (replacements, rows)
+    }
+
+    pub fn sort_rows(&mut self, reverse: bool, unique: bool) {
+        self.sort_range(0, self.rows.len().saturating_sub(1), reverse, unique);
+    }
+
+    pub fn sort_range(&mut self, start_row: usize, end_row: usize, reverse: bool, unique: bool) {
+        let mut rows: Vec<String> = self.rows[start_row..=end_row]
+            .iter()
+            .map(|r| r.content.clone())
+            .collect();
+
+        if reverse {
+            rows.sort_by(|a, b| b.cmp(a));
+        } else {
+            rows.sort();
+        }
+
+        if unique {
+            rows.dedup();
+        }
+
+        self.replace_rows(start_row, end_row - start_row + 1, rows);
+    }
+
+    pub fn delete_columns(
+        &mut self,
+        start_row: usize,
+        end_row: usize,
+        start_col: usize,
+        end_col: usize,
+    ) {
+        let rows = self.rows[start_row..=end_row]
+            .iter()
+            .map(|r| r.render(0, start_col) + &r.render(end_col + 1, r.len()))
+            .collect();
+
+        self.replace_rows(start_row, end_row - start_row + 1, rows);
+    }
+
+    pub fn insert_columns(&mut self, start_row: usize, end_row: usize, column: usize, text: &str) {
+        let rows = self.rows[start_row..=end_row]
+            .iter()
+            .map(|r| {
+                let padding = " ".repeat(column.saturating_sub(r.len()));
+
+                r.render(0, column) + &padding + text + &r.render(column, r.len())
+            })
+            .collect();
+
+        self.replace_rows(start_row, end_row - start_row + 1, rows);
+    }
+
+    pub fn delete_rows(&mut self, start_row: usize, end_row: usize) -> Vec<String> {
+        let deleted = self.rows[start_row..=end_row]
+            .iter()
+            .map(|r| r.content.clone())
+            .collect();
+
+        let replacement = if end_row - start_row + 1 == self.rows.len() {
+            vec![String::new()]
+        } else {
+            Vec::new()
+        };
+
+        self.replace_rows(start_row, end_row - start_row + 1, replacement);
+
+        deleted
+    }
+
+    pub fn copy_rows(&mut self, start_row: usize, end_row: usize, at: usize) {
+        let rows = self.rows[start_row..=end_row]
+            .iter()
+            .map(|r| r.content.clone())
+            .collect();
+
+        self.replace_rows(at, 0, rows);
+    }
+
+    /// Moves `start_row..=end_row` before row index `at` as one undo step;
+    /// `at` must lie outside the moved rows.
+    pub fn move_rows(&mut self, start_row: usize, end_row: usize, at: usize) {
+        let (span_start, span_end, split) = if at < start_row {
+            (at, end_row + 1, start_row - at)
+        } else {
+            (start_row, at, end_row + 1 - start_row)
+        };
+
+        let mut rows: Vec<String> = self.rows[span_start..span_end]
+            .iter()
+            .map(|r| r.content.clone())
+            .collect();
+
+        rows.rotate_left(split);
+
+        self.replace_rows(span_start, span_end - span_start, rows);
+    }
+
+    pub fn swap_rows(&mut self, a: usize, b: usize) {
+        if a == b || a.max(b) >= self.rows.len() {
+            return;
+        }
+
+        let (start_row, end_row) = (a.min(b), a.max(b));
+
+        let mut rows: Vec<String> = self.rows[start_row..=end_row]
+            .iter()
+            .map(|r| r.content.clone())
+            .collect();
+
+        let last = rows.len() - 1;
+
+        rows.swap(0, last);
+
+        self.replace_rows(start_row, end_row - start_row + 1, rows);
+    }
+
+    pub fn toggle_comment(&mut self, row: usize, comment: &str) {
+        self.toggle_comment_range(row, row, comment);
+    }
+
+    pub fn toggle_comment_range(&mut self, start_row: usize, end_row: usize, comment: &str) {
+        let rows = &self.rows[start_row..=end_row];
+
+        let is_blank = |content: &str| content.trim().is_empty();
+
+        let indent = rows
+            .iter()
+            .filter(|r| !is_blank(&r.content))
+            .map(|r| r.content.len() - r.content.trim_start_matches([' ', '\t']).len())
+            .min()
+            .unwrap_or(0);
+
+        let commented = rows.iter().filter(|r| !is_blank(&r.content)).all(|r| {
+            r.content
+                .trim_start_matches([' ', '\t'])
+                .starts_with(comment)
+        });
+
+        let result = rows
+            .iter()
+            .map(|r| {
+                if is_blank(&r.content) {
+                    return r.content.clone();
+                }
+
+                let leading = r.content.len() - r.content.trim_start_matches([' ', '\t']).len();
+
+                if commented {
+                    let body = &r.content[leading + comment.len()..];
+
+                    let body = body.strip_prefix(' ').unwrap_or(body);
+
+                    format!("{}{}", &r.content[..leading], body)
+                } else {
+                    format!(
+                        "{}{} {}",
+                        &r.content[..indent],
+                        comment,
+                        &r.content[indent..]
+                    )
+                }
+            })
+            .collect();
+
+        self.replace_rows(start_row, end_row - start_row + 1, result);
+    }
+
+    pub fn increment_at(&mut self, at: Position, delta: i64) -> Option<Position> {
+        let row = self.rows.get(at.row)?;
+
+        let (range, value) = row.find_number_at_or_after(at.column)?;
+
+        let number = row.render(range.start, range.end);
+
+        let value = value.saturating_add(delta);
+
+        let replacement = match number
+            .strip_prefix("0x")
+            .or_else(|| number.strip_prefix("0X"))
+        {
+            Some(digits) => {
+                let value = value.max(0);
+
+                let hex = if digits.chars().any(|c| c.is_ascii_uppercase()) {
+                    format!("{:X}", value)
+                } else {
+                    format!("{:x}", value)
+                };
+
+                format!("{}{:0>width$}", &number[..2], hex, width = digits.len())
+            }
+
+            None => {
+                let digits = number.trim_start_matches('-');
+
+                let width = if digits.len() > 1 && digits.starts_with('0') {
+                    digits.len()
+                } else {
+                    0
+                };
+
+                let sign = if value < 0 { "-" } else { "" };
+
+                format!("{}{:0>width$}", sign, value.unsigned_abs(), width = width)
+            }
+        };
+
+        let mut end = at;
+
+        end.column = range.start + replacement.len() - 1;
+
+        let result = row.render(0, range.start) + &replacement + &row.render(range.end, row.len());
+
+        self.replace_rows(at.row, 1, vec![result]);
+
+        Some(end)
+    }
+
+    pub fn undo(&mut self) -> Option<usize> {
+        if self.scratch {
+            self.edit_refused = true;
+
+            return None;
+        }
+
+        let row = self.history.undo(&mut self.rows)?;
+
+        self.modified = true;
+
+        self.swap_pending = true;
+
+        self.revision += 1;
+
+        Some(row)
+    }
+
+    pub fn redo(&mut self) -> Option<usize> {
+        if self.scratch {
+            self.edit_refused = true;
+
+            return None;
+        }
+
+        let row = self.history.redo(&mut self.rows)?;
+
+        self.modified = true;
+
+        self.swap_pending = true;
+
+        self.revision += 1;
+
+        Some(row)
+    }
+
+    pub fn detect_file_type(&self) -> FileType {
+        let path = self.path.as_deref().unwrap_or(Path::new(""));
+
+        let first_line = self.rows.first().map_or("", |row| row.content.as_str());
+
+        detect_file_type(path, first_line)
+    }
+
+    pub fn find_all(&self, term: &str, options: SearchOptions) -> Vec<Position> {
+        if term.is_empty() {
+            return Vec::new();
+        }
+
+        if options.ignore_case {
+            let regex = RegexBuilder::new(&regex::escape(term))
+                .case_insensitive(true)
+                .build();
+
+            return match regex {
+                Ok(regex) => self
+                    .find_all_regex(&regex)
+                    .into_iter()
+                    .map(|(start, _)| start)
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+        }
+
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row_index, row)| {
+                row.content
+                    .match_indices(term)
+                    .map(move |(index, _)| Position {
+                        row: row_index,
+                        column: row.column_at_byte(index),
+                        ..Default::default()
+                    })
+            })
+            .collect()
+    }
+
+    pub fn find_all_regex(&self, regex: &Regex) -> Vec<(Position, usize)> {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row_index, row)| {
+                regex.find_iter(&row.content).map(move |m| {
+                    let position = Position {
+                        row: row_index,
+                        column: row.column_at_byte(m.start()),
+                        ..Default::default()
+                    };
+
+                    (position, row.column_at_byte(m.end()))
+                })
+            })
+            .collect()
+    }
+
+    pub fn modeline(&self, lines: usize) -> Option<Modeline> {
+        let start = self.rows.len().saturating_sub(lines);
+
+        self.rows[start..]
+            .iter()
+            .rev()
+            .find_map(|row| Modeline::parse(&row.content))
+    }
+
+    pub fn detect_indent(&self, lines: usize) -> Option<Indent> {
+        let (mut tabs, mut spaces) = (0, 0);
+
+        let mut steps: HashMap<usize, usize> = HashMap::new();
+
+        let mut previous = 0;
+
+        for row in self
+            .rows
+            .iter()
+            .filter(|row| !row.content.trim().is_empty())
+            .take(lines)
+        {
+            if row.content.starts_with('\t') {
+                tabs += 1;
+
+                continue;
+            }
+
+            let indent = row.content.len() - row.content.trim_start_matches(' ').len();
+
+            if indent > 0 {
+                spaces += 1;
+            }
+
+            // Single-space steps are usually alignment, e.g. ` *` in comments.
+            let step = indent.abs_diff(previous);
+
+            if (2..=8).contains(&step) {
+                *steps.entry(step).or_default() += 1;
+            }
+
+            previous = indent;
+        }
+
+        if tabs == 0 && spaces == 0 {
+            return None;
+        }
+
+        let width = steps
+            .into_iter()
+            .max_by_key(|&(step, count)| (count, std::cmp::Reverse(step)))
+            .map(|(step, _)| step);
+
+        Some(Indent {
+            expand_tabs: spaces >= tabs,
+            width: width.filter(|_| spaces >= tabs),
+        })
+    }
+
+    /// The path with symlinks resolved, used for everything that touches the
+    /// disk; `path` is kept as given for display.
+    pub fn canonical_path(&self) -> Option<&Path> {
+        self.canonical_path.as_deref().or(self.path.as_deref())
+    }
+
+    pub fn changed_on_disk(&self) -> bool {
+        self.canonical_path().and_then(disk_stamp) != self.disk_stamp
+    }
+
+    pub fn save_conflicts(&self, path: Option<&Path>) -> bool {
+        match path {
+            Some(path) => disk_stamp(path).is_some(),
+            None => self.changed_on_disk(),
+        }
+    }
+
+    /// Saves to `path` and makes it the document's file. When the write
+    /// fails the document keeps the file it had.
+    pub fn save_as(
+        &mut self,
+        path: PathBuf,
+        force: bool,
+        final_newline: Option<bool>,
+    ) -> Result<usize> {
+        let previous_swap = self.swap_path();
+
+        let previous = (
+            self.path.take(),
+            self.canonical_path.take(),
+            self.disk_stamp.take(),
+        );
+
+        self.set_path(path);
+
+        let result = self.save(force, final_newline);
+
+        match result {
+            // The swap file of the old name, e.g. of an unnamed buffer,
+            // would otherwise be offered for recovery forever.
+            Ok(_) => {
+                let _ = fs::remove_file(previous_swap);
+            }
+
+            Err(_) => (self.path, self.canonical_path, self.disk_stamp) = previous,
+        }
+
+        result
+    }
+
+    pub fn save(&mut self, force: bool, final_newline: Option<bool>) -> Result<usize> {
+        let final_newline = final_newline.unwrap_or(self.final_newline);
+
+        if !force && self.changed_on_disk() {
+            return Err(DocumentError::ChangedOnDisk.into());
+        }
+
+        let path = self.canonical_path().unwrap().to_path_buf();
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        let temp_path = path.with_file_name(format!(".{}.tmp", name));
+
+        let n = match self.write_to(&temp_path, final_newline) {
+            Ok(n) => n,
+
+            Err(err) => {
+                let _ = fs::remove_file(&temp_path);
+
+                return Err(err.into());
+            }
+        };
+
+        if let Ok(metadata) = fs::metadata(&path) {
+            let _ = fs::set_permissions(&temp_path, metadata.permissions());
+        }
+
+        // Renaming over the target is atomic; where that is refused (e.g. on
+        // Windows) the contents are copied instead.
+        if fs::rename(&temp_path, &path).is_err() {
+            let copied = fs::copy(&temp_path, &path);
+
+            let _ = fs::remove_file(&temp_path);
+
+            copied?;
+        }
+
+        if self.canonical_path.is_none() {
+            self.canonical_path = self
+                .path
+                .as_ref()
+                .and_then(|path| fs::canonicalize(path).ok());
+        }
+
+        self.disk_stamp = self.canonical_path().and_then(disk_stamp);
+
+        self.modified = false;
+
+        self.final_newline = final_newline;
+
+        self.remove_swap();
+
+        Ok(n)
+    }
+
+    fn write_to(&self, path: &Path, final_newline: bool) -> io::Result<usize> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let mut n = 0;
+
+        let mut write = |text: &str| {
+            let bytes = self
+                .encoding
+                .encode(text)
+                .ok_or_else(|| io::Error::other(DocumentError::Unencodable(self.encoding)))?;
+
+            n += bytes.len();
+
+            writer.write_all(&bytes)
+        };
+
+        if self.has_bom {
+            write("\u{feff}")?;
+        }
+
+        for (i, row) in self.rows.iter().enumerate() {
+            write(&row.content)?;
+
+            if final_newline || i + 1 < self.rows.len() {
+                write(self.line_ending.as_str())?;
+            }
+        }
+
+        writer.into_inner()?.sync_all()?;
+
+        Ok(n)
+    }
+
+    pub fn replace_text(&mut self, text: &str) {
+        let mut rows: Vec<String> = text.lines().map(String::from).collect();
+
+        if rows.is_empty() {
+            rows.push(String::new());
+        }
+
+        let unchanged = rows.len() == self.rows.len()
+            && rows
+                .iter()
+                .zip(&self.rows)
+                .all(|(new, row)| *new == row.content);
+
+        if !unchanged {
+            self.replace_rows(0, self.rows.len(), rows);
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+
+        for row in &self.rows {
+            text.push_str(&row.content);
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn text_range(&self, start: Position, end: Position) -> String {
+        let (start, end) = if (end.row, end.column) < (start.row, start.column) {
+            (end, start)
+        } else {
+            (start, end)
+        };
+
+        let Some(last_row) = self.rows.len().checked_sub(1) else {
+            return String::new();
+        };
+
+        if start.row > last_row {
+            return String::new();
+        }
+
+        let (end_row, end_column) = if end.row > last_row {
+            (last_row, usize::MAX)
+        } else {
+            (end.row, end.column)
+        };
+
+        if start.row == end_row {
+            return self.rows[start.row].render(start.column, end_column);
+        }
+
+        let first = &self.rows[start.row];
+
+        let mut text = first.render(start.column, first.len());
+
+        for row in &self.rows[start.row + 1..end_row] {
+            text.push_str(self.line_ending.as_str());
+
+            text.push_str(&row.content);
+        }
+
+        text.push_str(self.line_ending.as_str());
+
+        text.push_str(&self.rows[end_row].render(0, end_column));
+
+        text
+    }
+
+    pub fn line_range_text(&self, start_row: usize, end_row: usize) -> String {
+        let (start_row, end_row) = (start_row.min(end_row), start_row.max(end_row));
+
+        self.rows
+            .iter()
+            .skip(start_row)
+            .take(end_row - start_row + 1)
+            .flat_map(|row| [row.content.as_str(), self.line_ending.as_str()])
+            .collect()
+    }
+
+    pub fn swap_path(&self) -> PathBuf {
+        match self.canonical_path() {
+            Some(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+                path.with_file_name(format!(".{}.wind.swp", name))
+            }
+
+            None => unnamed_swap_dir().join(format!(
+                "{}{}.swp",
+                unnamed_swap_prefix(),
+                self.unnamed_swap
+            )),
+        }
+    }
+
+    pub fn unnamed_swaps() -> Vec<(usize, PathBuf)> {
+        let Ok(entries) = fs::read_dir(unnamed_swap_dir()) else {
+            return Vec::new();
+        };
+
+        let prefix = unnamed_swap_prefix();
+
+        let mut swaps: Vec<(usize, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let index = entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix(&prefix)?
+                    .strip_suffix(".swp")?
+                    .parse()
+                    .ok()?;
+
+                Some((index, entry.path()))
+            })
+            .collect();
+
+        swaps.sort();
+
+        swaps
+    }
+
+    pub fn has_newer_swap(&self) -> bool {
+        let Some(path) = self.canonical_path() else {
+            return false;
+        };
+
+        let Ok(swap_modified) = fs::metadata(self.swap_path()).and_then(|m| m.modified()) else {
+            return false;
+        };
+
+        match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(file_modified) => swap_modified >= file_modified,
+            Err(_) => true,
+        }
+    }
+
+    pub fn write_swap(&mut self) -> Result<()> {
+        let swap_path = self.swap_path();
+
+        if let Some(dir) = swap_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let temp_path = swap_path.with_extension("swp.tmp");
+
+        fs::write(&temp_path, self.text())?;
+
+        fs::rename(temp_path, swap_path)?;
+
+        self.swap_pending = false;
+
+        Ok(())
+    }
+
+    pub fn recover_swap(&mut self) -> Result<()> {
+        self.restore(&self.swap_path())
+    }
+
+    pub fn restore(&mut self, path: &Path) -> Result<()> {
+        let contents = fs::read_to_string(path)?;
+
+        let mut rows: Vec<String> = contents.lines().map(str::to_owned).collect();
+
+        if rows.is_empty() {
+            rows.push(String::new());
+        }
+
+        self.replace_rows(0, self.rows.len(), rows);
+
+        Ok(())
+    }
+
+    pub fn remove_swap(&self) {
+        let _ = fs::remove_file(self.swap_path());
+    }
+
+    pub fn line_at_byte_offset(&self, offset: usize) -> usize {
+        let mut end = 0;
+
+        for (i, row) in self.rows.iter().enumerate() {
+            end += row.content.len() + 1;
+
+            if offset < end {
+                return i;
+            }
+        }
+
+        self.rows.len().saturating_sub(1)
+    }
+
+    pub fn byte_offset_of_line(&self, row: usize) -> usize {
+        self.rows
+            .iter()
+            .take(row)
+            .map(|r| r.content.len() + 1)
+            .sum()
+    }
+
+    #[inline]
     pub fn row_len(&self, index: usize) -> usize {
         match self.rows.get(index) {
             Some(row) => row.len(),
@@ -176,3 +1841,355 @@ impl Document {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn row(content: &str) -> Row {
+        Row::from(content.to_owned())
+    }
+
+    fn document(lines: &[&str]) -> Document {
+        let mut document = Document::open(None).unwrap();
+
+        document.rows = lines.iter().map(|line| row(line)).collect();
+
+        document
+    }
+
+    fn at(row: usize, column: usize) -> Position {
+        Position {
+            row,
+            column,
+            ..Default::default()
+        }
+    }
+
+    fn contents(document: &Document) -> Vec<&str> {
+        document
+            .rows
+            .iter()
+            .map(|row| row.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn width_counts_cells_and_len_counts_graphemes() {
+        assert_eq!((row("abc").len(), row("abc").width(4)), (3, 3));
+
+        assert_eq!((row("日本語").len(), row("日本語").width(4)), (3, 6));
+
+        assert_eq!((row("\tx").len(), row("\tx").width(4)), (2, 5));
+
+        assert_eq!((row("ab\tx").len(), row("ab\tx").width(4)), (4, 5));
+
+        assert_eq!((row("日\t語").len(), row("日\t語").width(8)), (3, 10));
+    }
+
+    #[test]
+    fn tabs_reach_the_next_tab_stop() {
+        let row = row("a\tb\tc");
+
+        let cells: Vec<usize> = (0..=5).map(|col| row.column_to_display(col, 4)).collect();
+
+        assert_eq!(cells, [0, 1, 4, 5, 8, 9]);
+
+        assert_eq!(row.display_to_column(2, 4), 1);
+        assert_eq!(row.display_to_column(4, 4), 2);
+        assert_eq!(row.display_to_column(7, 4), 3);
+
+        assert_eq!(row.render_expanded(0, 5, 4), "a   b   c");
+        assert_eq!(row.render_expanded(2, 5, 4), "b   c");
+    }
+
+    #[test]
+    fn columns_past_the_end_take_one_cell() {
+        assert_eq!(row("日本").column_to_display(3, 4), 5);
+    }
+
+    fn round_trip(name: &str, text: &str, final_newline: Option<bool>) -> String {
+        let path = env::temp_dir().join(format!("wind-{}-{}", process::id(), name));
+
+        fs::write(&path, text).unwrap();
+
+        let mut document = Document::open(Some(path.clone())).unwrap();
+
+        document.save(true, final_newline).unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        saved
+    }
+
+    #[test]
+    fn saving_keeps_the_final_newline_unless_told_otherwise() {
+        assert_eq!(round_trip("keep-with", "a\nb\n", None), "a\nb\n");
+        assert_eq!(round_trip("keep-without", "a\nb", None), "a\nb");
+    }
+
+    #[test]
+    fn insert_final_newline_adds_it() {
+        assert_eq!(round_trip("on-with", "a\nb\n", Some(true)), "a\nb\n");
+        assert_eq!(round_trip("on-without", "a\nb", Some(true)), "a\nb\n");
+    }
+
+    #[test]
+    fn no_insert_final_newline_removes_it() {
+        assert_eq!(round_trip("off-with", "a\nb\n", Some(false)), "a\nb");
+        assert_eq!(round_trip("off-without", "a\nb", Some(false)), "a\nb");
+    }
+
+    #[test]
+    fn a_failed_save_as_keeps_the_old_file() {
+        let path = env::temp_dir().join(format!("wind-{}-save-as", process::id()));
+
+        fs::write(&path, "a\n").unwrap();
+
+        let mut document = Document::open(Some(path.clone())).unwrap();
+
+        let missing = env::temp_dir().join("wind-missing-dir").join("file");
+
+        assert!(document.save_as(missing, false, None).is_err());
+
+        assert_eq!(document.path.as_deref(), Some(path.as_path()));
+        assert!(!document.changed_on_disk());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn deleting_in_a_single_empty_row_does_nothing() {
+        let mut document = document(&[""]);
+
+        document.delete(at(0, 0));
+        document.delete(at(0, 3));
+
+        assert_eq!(contents(&document), [""]);
+        assert!(!document.modified);
+    }
+
+    #[test]
+    fn deleting_at_the_end_of_the_last_row_does_nothing() {
+        let mut document = document(&["ab", "c"]);
+
+        document.delete(at(1, 1));
+
+        assert_eq!(contents(&document), ["ab", "c"]);
+
+        document.delete(at(0, 2));
+
+        assert_eq!(contents(&document), ["abc"]);
+    }
+
+    #[test]
+    fn sorting_orders_the_rows_as_one_change() {
+        let mut document = document(&["pear", "apple", "fig", "apple"]);
+
+        document.sort_rows(false, false);
+
+        assert_eq!(contents(&document), ["apple", "apple", "fig", "pear"]);
+
+        document.undo();
+
+        assert_eq!(contents(&document), ["pear", "apple", "fig", "apple"]);
+    }
+
+    #[test]
+    fn sorting_in_reverse_and_unique() {
+        let mut document = document(&["pear", "apple", "fig", "apple"]);
+
+        document.sort_rows(true, false);
+
+        assert_eq!(contents(&document), ["pear", "fig", "apple", "apple"]);
+
+        document.sort_rows(false, true);
+
+        assert_eq!(contents(&document), ["apple", "fig", "pear"]);
+    }
+
+    #[test]
+    fn sorting_a_range_leaves_the_other_rows() {
+        let mut document = document(&["c", "b", "b", "a", "0"]);
+
+        document.sort_range(1, 3, true, true);
+
+        assert_eq!(contents(&document), ["c", "b", "a", "0"]);
+    }
+
+    #[test]
+    fn row_insert_str_counts_graphemes() {
+        let mut row = row("héllo wörld");
+
+        row.insert_str(6, "👨‍👩‍👧 日本 ");
+
+        assert_eq!(row.content, "héllo 👨‍👩‍👧 日本 wörld");
+        assert_eq!(row.len(), 16);
+
+        row.insert_str(100, "!");
+
+        assert_eq!(row.content, "héllo 👨‍👩‍👧 日本 wörld!");
+    }
+
+    #[test]
+    fn insert_str_splits_rows_at_newlines() {
+        let mut document = document(&["añb", "z"]);
+
+        let end = document.insert_str(at(0, 1), "é🇯🇵").unwrap();
+
+        assert_eq!(contents(&document), ["aé🇯🇵ñb", "z"]);
+        assert_eq!((end.row, end.column), (0, 3));
+
+        let end = document.insert_str(at(0, 2), "日\n\n本語").unwrap();
+
+        assert_eq!(contents(&document), ["aé日", "", "本語🇯🇵ñb", "z"]);
+        assert_eq!((end.row, end.column), (2, 2));
+
+        document.undo();
+
+        assert_eq!(contents(&document), ["aé🇯🇵ñb", "z"]);
+    }
+
+    #[test]
+    fn text_range_within_one_row() {
+        let document = document(&["héllo wörld"]);
+
+        assert_eq!(document.text_range(at(0, 1), at(0, 5)), "éllo");
+        assert_eq!(document.text_range(at(0, 6), at(0, 99)), "wörld");
+        assert_eq!(document.text_range(at(0, 3), at(0, 3)), "");
+    }
+
+    #[test]
+    fn text_range_across_rows() {
+        let mut document = document(&["one", "twö", "three"]);
+
+        assert_eq!(document.text_range(at(0, 1), at(2, 2)), "ne\ntwö\nth");
+        assert_eq!(document.text_range(at(1, 3), at(2, 0)), "\n");
+        assert_eq!(document.text_range(at(1, 0), at(9, 0)), "twö\nthree");
+
+        document.line_ending = LineEnding::CrLf;
+
+        assert_eq!(document.text_range(at(0, 2), at(1, 1)), "e\r\nt");
+    }
+
+    #[test]
+    fn text_range_accepts_reversed_positions() {
+        let document = document(&["one", "twö", "three"]);
+
+        assert_eq!(
+            document.text_range(at(2, 2), at(0, 1)),
+            document.text_range(at(0, 1), at(2, 2))
+        );
+        assert_eq!(document.text_range(at(1, 3), at(1, 1)), "wö");
+    }
+
+    #[test]
+    fn line_range_text_takes_whole_rows() {
+        let document = document(&["one", "twö", "three"]);
+
+        assert_eq!(document.line_range_text(1, 1), "twö\n");
+        assert_eq!(document.line_range_text(2, 0), "one\ntwö\nthree\n");
+        assert_eq!(document.line_range_text(1, 9), "twö\nthree\n");
+    }
+
+    #[test]
+    fn delete_range_within_one_row() {
+        let mut document = document(&["héllo wörld", "next"]);
+
+        assert_eq!(document.delete_range(at(0, 1), at(0, 6)), "éllo ");
+        assert_eq!(contents(&document), ["hwörld", "next"]);
+
+        assert_eq!(document.delete_range(at(0, 2), at(0, 2)), "");
+        assert_eq!(contents(&document), ["hwörld", "next"]);
+    }
+
+    #[test]
+    fn delete_range_joins_adjacent_rows() {
+        let mut document = document(&["one", "twö", "three"]);
+
+        assert_eq!(document.delete_range(at(1, 2), at(0, 1)), "ne\ntw");
+        assert_eq!(contents(&document), ["oö", "three"]);
+
+        assert_eq!(document.delete_range(at(0, 2), at(1, 0)), "\n");
+        assert_eq!(contents(&document), ["oöthree"]);
+    }
+
+    #[test]
+    fn delete_range_removes_the_rows_between() {
+        let mut document = document(&["one", "two", "three", "four"]);
+
+        assert_eq!(
+            document.delete_range(at(0, 2), at(3, 1)),
+            "e\ntwo\nthree\nf"
+        );
+        assert_eq!(contents(&document), ["onour"]);
+
+        document.undo();
+
+        assert_eq!(contents(&document), ["one", "two", "three", "four"]);
+    }
+
+    #[test]
+    fn retab_turns_leading_tabs_into_spaces() {
+        let mut document = document(&["\tone", "  \ttwo\tx", "\t  three", "four\t"]);
+
+        document.retab(0, 3, 4, false);
+
+        assert_eq!(
+            contents(&document),
+            ["    one", "    two\tx", "      three", "four\t"]
+        );
+    }
+
+    #[test]
+    fn retab_turns_leading_spaces_into_tabs() {
+        let mut document = document(&["    one", "  \ttwo \t", "      three", "  four"]);
+
+        document.retab(0, 3, 4, true);
+
+        assert_eq!(
+            contents(&document),
+            ["\tone", "\ttwo \t", "\t  three", "  four"]
+        );
+
+        document.undo();
+
+        assert_eq!(
+            contents(&document),
+            ["    one", "  \ttwo \t", "      three", "  four"]
+        );
+    }
+
+    #[test]
+    fn retab_only_touches_the_range() {
+        let mut document = document(&["\ta", "\tb", "\tc"]);
+
+        document.retab(1, 1, 2, false);
+
+        assert_eq!(contents(&document), ["\ta", "  b", "\tc"]);
+    }
+
+    #[test]
+    fn an_empty_file_opens_with_one_row() {
+        let path = env::temp_dir().join(format!("wind-{}-empty", process::id()));
+
+        fs::write(&path, "").unwrap();
+
+        let mut document = Document::open(Some(path.clone())).unwrap();
+
+        assert_eq!(contents(&document), [""]);
+
+        document.insert(at(0, 0), 'a').unwrap();
+
+        assert_eq!(contents(&document), ["a"]);
+
+        document.save(false, None).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
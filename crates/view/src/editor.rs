@@ -1,133 +1,781 @@
 use crate::boundaries::Boundaries;
+use crate::buffers::BufferSwitch;
+use crate::config::{Config, CursorShape, EditorConfig, WrapIndent};
+use crate::diagnostics::{Diagnostic, DiagnosticSeverity};
 use crate::document::*;
+use crate::editorconfig::{EditorConfigParser, IndentStyle};
+use crate::fold::Folds;
+use crate::git::GitDiff;
+use crate::messages::MessageLog;
 use crate::position::*;
+use crate::positions::{load_position, save_position};
+use crate::quickfix::{QuickfixEntry, QuickfixList};
+use crate::recent::{load_recent, record_recent, RecentFiles};
+use crate::recovery::{find_recovery, write_recovery};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
-use std::path::PathBuf;
+use regex::{Regex, RegexBuilder};
 
-#[derive(PartialEq)]
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const INFO_MESSAGE_TIMEOUT: Duration = Duration::from_secs(3);
+const DETECT_INDENT_LINES: usize = 100;
+const SEARCH_HISTORY_LEN: usize = 50;
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(5);
+
+type LineRange = (usize, usize);
+
+#[derive(Default, PartialEq)]
 pub enum EditorStatus {
-    Message(String),
+    Message(String, Option<Instant>),
     Exit,
+    #[default]
     None,
 }
 
-impl Default for EditorStatus {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
-impl ToString for EditorStatus {
-    fn to_string(&self) -> String {
+impl fmt::Display for EditorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            EditorStatus::Message(msg) => msg.to_owned(),
-            EditorStatus::Exit => "".to_owned(),
-            EditorStatus::None => "".to_owned(),
+            EditorStatus::Message(msg, _) => write!(f, "{}", msg),
+            EditorStatus::Exit => Ok(()),
+            EditorStatus::None => Ok(()),
         }
     }
 }
 
+#[derive(Default, PartialEq)]
 pub enum EditorMode {
+    #[default]
     Normal,
     Insert,
+    Replace,
+    Visual,
+    VisualBlock,
+    Command,
+    Search,
 }
 
-impl Default for EditorMode {
-    fn default() -> Self {
-        Self::Normal
+impl fmt::Display for EditorMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditorMode::Normal => write!(f, "normal"),
+            EditorMode::Insert => write!(f, "insert"),
+            EditorMode::Replace => write!(f, "replace"),
+            EditorMode::Visual => write!(f, "visual"),
+            EditorMode::VisualBlock => write!(f, "visual block"),
+            EditorMode::Command => write!(f, "command"),
+            EditorMode::Search => write!(f, "search"),
+        }
     }
 }
 
-impl ToString for EditorMode {
-    fn to_string(&self) -> String {
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operator {
+    Delete,
+    Change,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Motion {
+    WordStart,
+    WordEnd,
+    LineStart,
+    LineEnd,
+    Down,
+    Up,
+    Line,
+}
+
+#[derive(Clone)]
+pub enum LastChange {
+    DeleteChar {
+        count: usize,
+    },
+    Operator {
+        operator: Operator,
+        motion: Motion,
+        count: usize,
+        inserted: String,
+    },
+    Insert {
+        inserted: String,
+    },
+}
+
+impl LastChange {
+    fn inserted_mut(&mut self) -> Option<&mut String> {
         match self {
-            EditorMode::Normal => "normal".to_owned(),
-            EditorMode::Insert => "insert".to_owned(),
+            LastChange::Insert { inserted } | LastChange::Operator { inserted, .. } => {
+                Some(inserted)
+            }
+            LastChange::DeleteChar { .. } => None,
         }
     }
 }
 
 #[derive(Default)]
 pub struct Editor {
-    pub document: Document,
+    document: Document,
     pub position: Position,
-    pub scroll_offset: Position,
+    scroll_offset: Position,
     pub status: EditorStatus,
     pub mode: EditorMode,
+    pub visual_anchor: Option<Position>,
+    pub command_line: String,
+    pub config: EditorConfig,
+    pub register: String,
+    pub pending_clipboard: bool,
+    pub replaced: Vec<Option<String>>,
+    pub block_insert: Option<(usize, usize, Position)>,
+    pub messages: MessageLog,
+    pub overlay: Vec<String>,
+    pub recent_files: Option<RecentFiles>,
+    pub quickfix: QuickfixList,
+    pub pending_grep: Option<String>,
+    pub message_time: Option<Instant>,
+    pub recovery_pending: bool,
+    pub recovery_file: Option<PathBuf>,
+    pub git_diff: GitDiff,
+    pub diagnostics: Vec<Diagnostic>,
+    pub user_config: Config,
+    pub pending_git_diff: bool,
+    pub saved: bool,
+    pub last_change: Option<LastChange>,
+    pub hover_popup: Option<String>,
+    pub pending_help: bool,
+    pub pending_buffer: Option<BufferSwitch>,
+    help_return: Option<Box<HelpReturn>>,
+    recording: Option<LastChange>,
+    pub split: Option<Split>,
+    pub search_term: Option<String>,
+    search_regex: Option<Regex>,
+    search_options: SearchOptions,
+    search_origin: Option<SearchOrigin>,
+    pub search_failed: bool,
+    pub search_history: VecDeque<String>,
+    search_history_index: Option<usize>,
+    search_draft: String,
+    pub folds: Folds,
+    pub highlight_active: bool,
+    search_matches: Vec<(Position, usize)>,
+    search_revision: Option<u64>,
+}
+
+struct SearchOrigin {
+    position: Position,
+    scroll_offset: Position,
+    term: Option<String>,
+    regex: Option<Regex>,
+    options: SearchOptions,
+    highlight_active: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct Split {
+    pub vertical: bool,
+    pub focus_first: bool,
+    pub position: Position,
+    pub scroll_offset: Position,
+}
+
+struct HelpReturn {
+    document: Document,
+    position: Position,
+    scroll_offset: Position,
+    git_diff: GitDiff,
+    diagnostics: Vec<Diagnostic>,
+    folds: Folds,
 }
 
 impl Editor {
-    pub fn new(file_path: Option<PathBuf>) -> Result<Editor> {
-        Ok(Editor {
-            document: Document::open(file_path)?,
+    pub fn new(file_path: Option<PathBuf>, config_path: Option<&Path>) -> Result<Editor> {
+        let (document, error) = match file_path {
+            Some(path) if path.as_os_str() == "-" && io::stdin().is_terminal() => (
+                Document::open(None)?,
+                Some("Nothing piped to stdin, starting an empty buffer".to_owned()),
+            ),
+
+            Some(path) if path.as_os_str() == "-" => (Document::from_stdin()?, None),
+
+            file_path => match Document::open(file_path) {
+                Ok(document) => (document, None),
+
+                Err(err) if matches!(err.downcast_ref(), Some(DocumentError::IsDirectory(_))) => {
+                    (Document::open(None)?, Some(err.to_string()))
+                }
+
+                Err(err) => return Err(err),
+            },
+        };
+
+        let mut user_config = match config_path {
+            Some(path) => Config::load_from(path, true),
+            None => Config::load(),
+        };
+
+        let warnings = std::mem::take(&mut user_config.warnings);
+
+        let mut editor = Editor {
+            document,
+            user_config,
             ..Default::default()
-        })
+        };
+
+        editor.config = editor.file_config();
+
+        editor.apply_editorconfig();
+
+        editor.report_indent();
+
+        editor.report_lossy();
+
+        if !warnings.is_empty() {
+            for warning in &warnings {
+                editor.messages.push(warning.clone());
+            }
+
+            editor.set_message(format!(
+                "{} problem(s) in config.toml, see :messages",
+                warnings.len()
+            ));
+        }
+
+        if let Some(error) = error {
+            editor.set_message(error);
+        }
+
+        editor.restore_position();
+
+        editor.record_recent();
+
+        editor.check_swap();
+
+        editor.pending_git_diff = editor.document.path.is_some();
+
+        Ok(editor)
+    }
+
+    #[inline]
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    #[inline]
+    pub fn document_mut(&mut self) -> &mut Document {
+        &mut self.document
+    }
+
+    #[inline]
+    pub fn scroll_offset(&self) -> Position {
+        self.scroll_offset
+    }
+
+    fn check_swap(&mut self) {
+        if self.document.path.is_none() {
+            self.check_unnamed_swap();
+
+            return;
+        }
+
+        if !self.document.has_newer_swap() {
+            return;
+        }
+
+        self.recovery_pending = true;
+
+        self.set_message(format!(
+            "Found swap file {}: recover it? (y/n)",
+            self.document.swap_path().display()
+        ));
+    }
+
+    fn check_unnamed_swap(&mut self) {
+        let swaps = Document::unnamed_swaps();
+
+        let Some((index, path)) = swaps.first() else {
+            return;
+        };
+
+        self.document.unnamed_swap = *index;
+
+        self.recovery_pending = true;
+
+        for (_, path) in &swaps {
+            self.messages.push(format!(
+                "Swap file of an unnamed buffer: {}",
+                path.display()
+            ));
+        }
+
+        let others = match swaps.len() {
+            1 => String::new(),
+            len => format!(" ({} more in :messages)", len - 1),
+        };
+
+        self.set_message(format!(
+            "Found swap file {}{}: recover it? (y/n)",
+            path.display(),
+            others
+        ));
+    }
+
+    pub fn answer_recovery(&mut self, recover: bool) {
+        self.recovery_pending = false;
+
+        if !recover {
+            self.document.remove_swap();
+
+            self.clear_message();
+
+            return;
+        }
+
+        match self.document.recover_swap() {
+            Ok(()) => self.set_info("Recovered from swap file".to_owned()),
+            Err(err) => self.set_message(format!("Could not recover swap file: {}", err)),
+        }
+    }
+
+    pub fn check_recovery(&mut self) {
+        if self.recovery_pending {
+            return;
+        }
+
+        self.recovery_file = find_recovery(&self.document);
+
+        if self.recovery_file.is_none() {
+            return;
+        }
+
+        let name = self
+            .document
+            .path
+            .as_deref()
+            .and_then(Path::file_name)
+            .unwrap_or_default()
+            .to_string_lossy();
+
+        self.set_message(format!(
+            "Recovery file found for {}; press R to restore",
+            name
+        ));
+    }
+
+    pub fn answer_recovery_file(&mut self, restore: bool) {
+        let Some(path) = self.recovery_file.take() else {
+            return;
+        };
+
+        if !restore {
+            self.clear_message();
+
+            return;
+        }
+
+        match self.document.restore(&path) {
+            Ok(()) => {
+                let _ = fs::remove_file(&path);
+
+                self.set_info("Restored from recovery file".to_owned());
+            }
+
+            Err(err) => self.set_message(format!("Could not restore recovery file: {}", err)),
+        }
+    }
+
+    pub fn write_recovery(&self) {
+        let help_document = self.help_return.as_ref().map(|previous| &previous.document);
+
+        for document in [Some(&self.document), help_document].into_iter().flatten() {
+            if document.modified {
+                let _ = write_recovery(document);
+            }
+        }
+    }
+
+    pub fn write_swap(&mut self) {
+        if self.recovery_pending || !self.document.modified || !self.document.swap_pending {
+            return;
+        }
+
+        if let Err(err) = self.document.write_swap() {
+            self.set_message(format!("Could not write swap file: {}", err));
+        }
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        let expiry = (self.config.message_timeout > 0)
+            .then(|| Instant::now() + Duration::from_secs(self.config.message_timeout));
+
+        self.show_message(message, expiry);
+    }
+
+    pub fn set_info(&mut self, message: String) {
+        self.show_message(message, Some(Instant::now() + INFO_MESSAGE_TIMEOUT));
+    }
+
+    fn show_message(&mut self, message: String, expiry: Option<Instant>) {
+        self.messages.push(message.clone());
+
+        self.status = EditorStatus::Message(message, expiry);
+
+        self.message_time = Some(Instant::now());
+    }
+
+    pub fn clear_message(&mut self) {
+        if matches!(self.status, EditorStatus::Message(..)) {
+            self.status = EditorStatus::None;
+        }
+
+        self.message_time = None;
+    }
+
+    pub fn message_deadline(&self) -> Option<Instant> {
+        match self.status {
+            EditorStatus::Message(_, expiry) => expiry,
+            _ => None,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: EditorMode) {
+        if self.document.scratch && matches!(mode, EditorMode::Insert | EditorMode::Replace) {
+            self.document.edit_refused = true;
+
+            return;
+        }
+
+        if mode != EditorMode::Insert {
+            self.finish_block_insert();
+
+            if let Some(change) = self.recording.take() {
+                self.last_change = Some(change);
+            }
+        }
+
+        match mode {
+            EditorMode::Insert | EditorMode::Replace => self.document.history.begin_group(),
+            _ => self.document.history.end_group(),
+        }
+
+        self.replaced.clear();
+
+        if matches!(mode, EditorMode::Command | EditorMode::Search) {
+            self.command_line.clear();
+        }
+
+        self.search_origin = match mode {
+            EditorMode::Search => Some(SearchOrigin {
+                position: self.position,
+                scroll_offset: self.scroll_offset,
+                term: self.search_term.clone(),
+                regex: self.search_regex.clone(),
+                options: self.search_options,
+                highlight_active: self.highlight_active,
+            }),
+            _ => None,
+        };
+
+        self.search_failed = false;
+
+        self.search_history_index = None;
+
+        self.visual_anchor = match mode {
+            EditorMode::Visual | EditorMode::VisualBlock => Some(self.position),
+            EditorMode::Command => self.visual_anchor,
+            _ => None,
+        };
+
+        self.mode = mode;
+    }
+
+    pub fn cursor_shape(&self) -> CursorShape {
+        match self.mode {
+            EditorMode::Normal => self.config.normal_cursor,
+            EditorMode::Insert | EditorMode::Command | EditorMode::Search => {
+                self.config.insert_cursor
+            }
+            EditorMode::Replace => self.config.replace_cursor,
+            EditorMode::Visual | EditorMode::VisualBlock => self.config.visual_cursor,
+        }
+    }
+
+    pub fn selected_range(&self) -> Option<(Position, Position)> {
+        let anchor = self.visual_anchor?;
+
+        if (anchor.row, anchor.column) <= (self.position.row, self.position.column) {
+            Some((anchor, self.position))
+        } else {
+            Some((self.position, anchor))
+        }
+    }
+
+    pub fn selected_block(&self) -> Option<(usize, usize, usize, usize)> {
+        if self.mode != EditorMode::VisualBlock {
+            return None;
+        }
+
+        let anchor = self.visual_anchor?;
+
+        Some((
+            anchor.row.min(self.position.row),
+            anchor.row.max(self.position.row),
+            anchor.column.min(self.position.column),
+            anchor.column.max(self.position.column),
+        ))
+    }
+
+    pub fn selected_columns(&self, row: usize) -> Option<(usize, usize)> {
+        if let Some((start_row, end_row, start_col, end_col)) = self.selected_block() {
+            return (start_row..=end_row)
+                .contains(&row)
+                .then_some((start_col, end_col + 1));
+        }
+
+        let (from, to) = self.selected_range()?;
+
+        if row < from.row || row > to.row {
+            return None;
+        }
+
+        let start = if row == from.row { from.column } else { 0 };
+
+        let end = if row == to.row {
+            to.column.saturating_add(1)
+        } else {
+            self.document.row_len(row)
+        };
+
+        Some((start, end))
+    }
+
+    pub fn selection_size(&self) -> Option<(usize, usize)> {
+        if let Some((start_row, end_row, start_col, end_col)) = self.selected_block() {
+            return Some((end_row - start_row + 1, end_col - start_col + 1));
+        }
+
+        let (from, to) = self.selected_range()?;
+
+        let characters = (from.row..=to.row)
+            .filter_map(|row| {
+                let (start, end) = self.selected_columns(row)?;
+
+                let line_break = usize::from(row < to.row);
+
+                Some(end.min(self.document.row_len(row)).saturating_sub(start) + line_break)
+            })
+            .sum();
+
+        Some((to.row - from.row + 1, characters))
+    }
+
+    pub fn delete_block(&mut self) {
+        if let Some((start_row, end_row, start_col, end_col)) = self.selected_block() {
+            self.document
+                .delete_columns(start_row, end_row, start_col, end_col);
+
+            self.position.row = start_row;
+
+            self.position.column = start_col.min(self.document.row_len(start_row));
+
+            self.position.history.column = self.position.column;
+        }
+
+        self.set_mode(EditorMode::Normal);
+    }
+
+    pub fn insert_block(&mut self) {
+        let Some((start_row, end_row, start_col, _)) = self.selected_block() else {
+            return;
+        };
+
+        self.set_mode(EditorMode::Insert);
+
+        self.position.row = start_row;
+
+        self.position.column = start_col.min(self.document.row_len(start_row));
+
+        self.position.history.column = self.position.column;
+
+        self.block_insert = Some((start_row + 1, end_row, self.position));
+    }
+
+    fn finish_block_insert(&mut self) {
+        let Some((start_row, end_row, start)) = self.block_insert.take() else {
+            return;
+        };
+
+        if self.position.row != start.row || self.position.column <= start.column {
+            return;
+        }
+
+        let text = self.document.rows[start.row].render(start.column, self.position.column);
+
+        if start_row <= end_row {
+            self.document
+                .insert_columns(start_row, end_row, start.column, &text);
+        }
+    }
+
+    fn follow_column(&mut self, boundaries: Boundaries) {
+        let width = boundaries.width as usize;
+
+        let mut offset = self.scroll_offset.history.column;
+
+        if self.position.column < offset {
+            offset = if self.position.column < width {
+                0
+            } else {
+                self.position.column + 1 - width
+            };
+        }
+
+        self.scroll_offset.column = offset;
+
+        self.scroll_column_into_view(boundaries);
     }
 
     pub fn move_up(&mut self, boundaries: Boundaries, offset: usize) -> Result<()> {
-        if self.position.row > 0 {
-            if self.position.row <= self.scroll_offset.row {
-                self.scroll_offset.row = self.scroll_offset.row.saturating_sub(offset);
+        // Count a closed fold as one row.
+        let offset = match self.folds.is_empty() {
+            true => offset,
+            false => {
+                self.position.row
+                    - (0..offset).fold(self.position.row, |row, _| {
+                        self.folds.visible(row.saturating_sub(1))
+                    })
             }
+        };
 
+        // An accelerated or counted move stops at the first row.
+        let offset = offset.min(self.position.row);
+
+        if offset > 0 {
             self.position.row -= offset;
 
+            if self.position.row < self.scroll_offset.row {
+                self.scroll_offset.row = self.position.row;
+            }
+
+            self.scroll_offset.row = self.folds.visible(self.scroll_offset.row);
+
             self.position.column = self
                 .position
                 .history
                 .column
                 .min(self.document.row_len(self.position.row));
 
-            if self.position.column < self.scroll_offset.column {
-                self.scroll_offset.column = 0;
-            } else if self.position.column >= self.scroll_offset.column + boundaries.width as usize
-            {
-                self.scroll_offset.column = self.position.column - boundaries.width as usize + 1;
-            }
+            self.follow_column(boundaries);
+        }
+
+        if self.config.wrap {
+            self.scroll_wrapped_into_view(boundaries);
         }
 
         Ok(())
     }
 
     pub fn move_down(&mut self, boundaries: Boundaries, offset: usize) -> Result<()> {
-        if self.position.row.saturating_add(offset) < self.document.rows.len() {
-            if self.position.row >= self.scroll_offset.row + boundaries.height as usize - offset {
+        let last = self.document.rows.len().saturating_sub(1);
+
+        // Count a closed fold as one row; an accelerated or counted move
+        // stops at the last row.
+        let offset = match self.folds.is_empty() {
+            true => offset.min(last.saturating_sub(self.position.row)),
+            false => {
+                let row = (0..offset).fold(self.position.row, |row, _| {
+                    match self.folds.closed_at(row).map_or(row, |fold| fold.end) + 1 {
+                        next if next <= last => next,
+                        _ => row,
+                    }
+                });
+
+                row - self.position.row
+            }
+        };
+
+        if offset > 0 {
+            let height = (boundaries.height as usize).max(1);
+
+            if self.position.row >= (self.scroll_offset.row + height).saturating_sub(offset) {
                 self.scroll_offset.row += offset;
             }
 
             self.position.row += offset;
 
+            if !self.folds.is_empty() {
+                self.scroll_rows_into_view(boundaries);
+            }
+
             self.position.column = self
                 .position
                 .history
                 .column
                 .min(self.document.row_len(self.position.row));
 
-            if self.position.column < self.scroll_offset.column {
-                self.scroll_offset.column = 0;
-            } else if self.position.column >= self.scroll_offset.column + boundaries.width as usize
-            {
-                self.scroll_offset.column = self.position.column - boundaries.width as usize + 1;
-            }
+            self.follow_column(boundaries);
+        }
+
+        if self.config.wrap {
+            self.scroll_wrapped_into_view(boundaries);
         }
 
         Ok(())
     }
 
+    pub fn move_to_line_start(&mut self, boundaries: Boundaries, visual: bool) -> Result<()> {
+        let (start, _) = self.line_span(boundaries, visual);
+
+        self.move_left(boundaries, self.position.column.saturating_sub(start))
+    }
+
+    pub fn move_to_line_end(&mut self, boundaries: Boundaries, visual: bool) -> Result<()> {
+        let (_, end) = self.line_span(boundaries, visual);
+
+        self.move_right(boundaries, end.saturating_sub(self.position.column))
+    }
+
+    fn line_span(&self, boundaries: Boundaries, visual: bool) -> (usize, usize) {
+        let len = self.document.row_len(self.position.row);
+
+        let Some(row) = self
+            .document
+            .rows
+            .get(self.position.row)
+            .filter(|_| visual && self.config.wrap)
+        else {
+            return (0, len);
+        };
+
+        let (starts, _) = self.wrap_row(row, boundaries.width as usize);
+
+        let segment = starts
+            .iter()
+            .rposition(|&start| start <= self.position.column)
+            .unwrap_or(0);
+
+        match starts.get(segment + 1) {
+            Some(&next) => (starts[segment], next - 1),
+            None => (starts[segment], len),
+        }
+    }
+
     pub fn move_left(&mut self, boundaries: Boundaries, offset: usize) -> Result<()> {
         if self.position.column > 0 {
             self.position.column = self.position.column.saturating_sub(offset);
 
             self.position.history.column = self.position.column;
 
-            if self.position.column < self.scroll_offset.column {
-                self.scroll_offset.column = self
-                    .position
-                    .column
-                    .saturating_sub(boundaries.width as usize);
-            }
+            self.scroll_column_into_view(boundaries);
         } else if offset != 0 {
             if self.position.row == self.scroll_offset.row && self.scroll_offset.row > 0 {
                 self.scroll_offset.row -= 1;
@@ -140,13 +788,16 @@ impl Editor {
 
                 self.position.history.column = self.position.column;
 
-                if self.position.column >= self.scroll_offset.column + boundaries.width as usize {
-                    self.scroll_offset.column =
-                        self.position.column + boundaries.width as usize - offset;
-                }
+                self.scroll_column_into_view(boundaries);
             }
         }
 
+        self.scroll_offset.history.column = self.scroll_offset.column;
+
+        if self.config.wrap {
+            self.scroll_wrapped_into_view(boundaries);
+        }
+
         Ok(())
     }
 
@@ -158,9 +809,7 @@ impl Editor {
 
             self.position.history.column = self.position.column;
 
-            if self.position.column >= self.scroll_offset.column + boundaries.width as usize {
-                self.scroll_offset.column += offset;
-            }
+            self.scroll_column_into_view(boundaries);
         } else if offset != 0 {
             if self.position.row
                 >= self
@@ -188,28 +837,2878 @@ impl Editor {
             }
         }
 
+        self.scroll_offset.history.column = self.scroll_offset.column;
+
+        if self.config.wrap {
+            self.scroll_wrapped_into_view(boundaries);
+        }
+
         Ok(())
     }
 
-    pub fn save(&mut self) {
-        if self.document.path.is_none() {
-            self.document.path = Some(PathBuf::from("temp"))
+    pub fn scroll_into_view(&mut self, boundaries: Boundaries) {
+        if self.config.wrap {
+            self.scroll_wrapped_into_view(boundaries);
+
+            return;
         }
 
-        match self.document.save() {
-            Ok(n) => {
-                self.status = EditorStatus::Message(format!(
-                    "'{}' saved, {}L {}B",
-                    self.document.path.as_ref().unwrap().display(),
-                    self.document.rows.len(),
-                    n
-                ));
-            }
+        self.scroll_rows_into_view(boundaries);
 
-            Err(err) => {
-                self.status =
-                    EditorStatus::Message(format!("Could not save the document: {}", err));
-            }
+        self.scroll_column_into_view(boundaries);
+
+        self.scroll_offset.history.column = self.scroll_offset.column;
+    }
+
+    fn scroll_rows_into_view(&mut self, boundaries: Boundaries) {
+        let height = boundaries.height as usize;
+
+        if self.position.row < self.scroll_offset.row {
+            self.scroll_offset.row = self.folds.visible(self.position.row);
+        } else if self.folds.is_empty() {
+            if self.position.row >= self.scroll_offset.row + height {
+                self.scroll_offset.row = (self.position.row + 1).saturating_sub(height);
+            }
+        } else {
+            // Walk up from the cursor a screenful of rows, counting each
+            // closed fold once.
+            let mut top = self.folds.visible(self.position.row);
+
+            for _ in 1..height {
+                if top == 0 {
+                    break;
+                }
+
+                top = self.folds.visible(top - 1);
+            }
+
+            self.scroll_offset.row = self.folds.visible(self.scroll_offset.row).max(top);
+        }
+    }
+
+    pub fn visual_cursor(&self, boundaries: Boundaries) -> (usize, usize) {
+        let display = |column: usize| {
+            self.document
+                .rows
+                .get(self.position.row)
+                .map_or(column, |row| {
+                    row.column_to_display(column, self.config.tab_width)
+                })
+        };
+
+        if !self.config.wrap {
+            return (
+                self.folds
+                    .visible_rows(self.scroll_offset.row, self.position.row),
+                display(self.position.column).saturating_sub(display(self.scroll_offset.column)),
+            );
+        }
+
+        let width = boundaries.width as usize;
+
+        let rows_above: usize = self
+            .document
+            .rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll_offset.row)
+            .take(self.position.row.saturating_sub(self.scroll_offset.row))
+            .filter(|(index, _)| !self.folds.is_hidden(*index))
+            .map(|(index, row)| match self.folds.closed_at(index) {
+                Some(_) => 1,
+                None => self.wrap_row(row, width).0.len(),
+            })
+            .sum();
+
+        let (starts, indent) = self
+            .document
+            .rows
+            .get(self.position.row)
+            .map(|row| self.wrap_row(row, width))
+            .unwrap_or_else(|| (vec![0], 0));
+
+        let segment = starts
+            .iter()
+            .rposition(|&start| start <= self.position.column)
+            .unwrap_or(0);
+
+        let indent = if segment > 0 { indent } else { 0 };
+
+        (
+            rows_above + segment,
+            (indent + display(self.position.column) - display(starts[segment]))
+                .min(width.saturating_sub(1)),
+        )
+    }
+
+    /// Where each screen line of `row` starts when wrapped at `width`, and
+    /// how far the lines after the first are indented by `wrap_indent`. An
+    /// indent leaving less than half the width is dropped.
+    pub fn wrap_row(&self, row: &Row, width: usize) -> (Vec<usize>, usize) {
+        let leading = row.leading_whitespace().graphemes(true).count();
+
+        let indent = match self.config.wrap_indent {
+            WrapIndent::None => 0,
+            WrapIndent::SameIndent => row.column_to_display(leading, self.config.tab_width),
+            WrapIndent::FirstNonWhitespace => leading,
+        };
+
+        let indent = if indent * 2 > width { 0 } else { indent };
+
+        (row.wrap(width, indent), indent)
+    }
+
+    pub fn position_at(&self, boundaries: Boundaries, (y, x): (usize, usize)) -> Position {
+        let width = boundaries.width as usize;
+
+        let mut lines = 0;
+
+        let mut last = None;
+
+        for (index, row) in self
+            .document
+            .rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll_offset.row)
+            .filter(|(index, _)| !self.folds.is_hidden(*index))
+        {
+            let (starts, indent) = match self.folds.closed_at(index) {
+                Some(_) => (vec![0], 0),
+                None if self.config.wrap => self.wrap_row(row, width),
+                None => (vec![self.scroll_offset.column], 0),
+            };
+
+            if y < lines + starts.len() {
+                let segment = y - lines;
+
+                let start = starts[segment];
+
+                let end = starts
+                    .get(segment + 1)
+                    .map_or(row.len(), |next| next.saturating_sub(1));
+
+                let x = match segment {
+                    0 => x,
+                    _ => x.saturating_sub(indent),
+                };
+
+                let column = row
+                    .display_to_column(
+                        row.column_to_display(start, self.config.tab_width) + x,
+                        self.config.tab_width,
+                    )
+                    .clamp(start.min(end), end);
+
+                return Position {
+                    row: index,
+                    column,
+                    ..Default::default()
+                };
+            }
+
+            lines += starts.len();
+
+            last = Some(index);
+        }
+
+        let row = last.unwrap_or(self.document.rows.len().saturating_sub(1));
+
+        Position {
+            row,
+            column: self.document.row_len(row),
+            ..Default::default()
+        }
+    }
+
+    pub fn move_to_screen(&mut self, boundaries: Boundaries, (y, x): (isize, usize)) {
+        let position = match usize::try_from(y) {
+            Ok(y) => self.position_at(boundaries, (y, x)),
+
+            Err(_) => {
+                let row = self.folds.visible(self.scroll_offset.row.saturating_sub(1));
+
+                Position {
+                    row,
+                    column: self.position.column.min(self.document.row_len(row)),
+                    ..Default::default()
+                }
+            }
+        };
+
+        self.position.row = position.row;
+
+        self.position.column = position.column;
+
+        self.position.history.column = position.column;
+
+        self.scroll_into_view(boundaries);
+    }
+
+    pub fn move_display_lines(
+        &mut self,
+        boundaries: Boundaries,
+        down: bool,
+        count: usize,
+    ) -> Result<()> {
+        if !self.config.wrap {
+            return match down {
+                true => self.move_down(boundaries, count),
+                false => self.move_up(boundaries, count),
+            };
+        }
+
+        let width = boundaries.width as usize;
+
+        // Wrapped lines of `row`, or just its start when a closed fold shows
+        // it as one line.
+        let starts = |row: usize| match self.folds.closed_at(row) {
+            Some(_) => (vec![0], 0),
+            None => self.wrap_row(&self.document.rows[row], width),
+        };
+
+        if self.position.row >= self.document.rows.len() {
+            return Ok(());
+        }
+
+        let (mut starts_of_row, mut indent) = starts(self.position.row);
+
+        let mut segment = starts_of_row
+            .iter()
+            .rposition(|&start| start <= self.position.column)
+            .unwrap_or(0);
+
+        // The screen column, kept across lines with different indents.
+        let x = self.visual_cursor(boundaries).1;
+
+        let mut target = self.position.row;
+
+        for _ in 0..count {
+            if down {
+                if segment + 1 < starts_of_row.len() {
+                    segment += 1;
+
+                    continue;
+                }
+
+                let next = self.folds.closed_at(target).map_or(target, |fold| fold.end) + 1;
+
+                if next >= self.document.rows.len() {
+                    break;
+                }
+
+                target = next;
+
+                (starts_of_row, indent) = starts(target);
+
+                segment = 0;
+            } else {
+                if segment > 0 {
+                    segment -= 1;
+
+                    continue;
+                }
+
+                if target == 0 {
+                    break;
+                }
+
+                target = self.folds.visible(target - 1);
+
+                (starts_of_row, indent) = starts(target);
+
+                segment = starts_of_row.len() - 1;
+            }
+        }
+
+        let row = &self.document.rows[target];
+
+        let start = starts_of_row[segment];
+
+        let end = starts_of_row
+            .get(segment + 1)
+            .map_or(row.len(), |next| next.saturating_sub(1));
+
+        let x = match segment {
+            0 => x,
+            _ => x.saturating_sub(indent),
+        };
+
+        self.position.row = target;
+
+        self.position.column = row
+            .display_to_column(
+                row.column_to_display(start, self.config.tab_width) + x,
+                self.config.tab_width,
+            )
+            .clamp(start.min(end), end);
+
+        self.position.history.column = self.position.column;
+
+        self.scroll_wrapped_into_view(boundaries);
+
+        Ok(())
+    }
+
+    pub fn select_word(&mut self, boundaries: Boundaries) {
+        let Some(row) = self.document.rows.get(self.position.row) else {
+            return;
+        };
+
+        let (start, end) = row.word_bounds(self.position.column);
+
+        self.select_columns(boundaries, start, end);
+    }
+
+    pub fn select_line(&mut self, boundaries: Boundaries) {
+        let end = self.document.row_len(self.position.row);
+
+        self.select_columns(boundaries, 0, end);
+    }
+
+    fn select_columns(&mut self, boundaries: Boundaries, start: usize, end: usize) {
+        self.position.column = start;
+
+        self.set_mode(EditorMode::Visual);
+
+        self.position.column = end.saturating_sub(1).max(start);
+
+        self.position.history.column = self.position.column;
+
+        self.scroll_into_view(boundaries);
+    }
+
+    fn scroll_wrapped_into_view(&mut self, boundaries: Boundaries) {
+        self.scroll_offset.column = 0;
+
+        self.scroll_offset.history.column = 0;
+
+        if self.position.row < self.scroll_offset.row {
+            self.scroll_offset.row = self.folds.visible(self.position.row);
+        }
+
+        let (width, height) = (boundaries.width as usize, boundaries.height as usize);
+
+        let lines_of = |row: usize| match self.folds.closed_at(row) {
+            Some(_) => 1,
+            None => self.wrap_row(&self.document.rows[row], width).0.len(),
+        };
+
+        let Some(cursor_row) = self.document.rows.get(self.position.row) else {
+            return;
+        };
+
+        let top = self.folds.visible(self.position.row);
+
+        // Screen lines from the top of the cursor's row down to the cursor.
+        let mut used = match self.folds.closed_at(top) {
+            Some(_) => 1,
+            None => self
+                .wrap_row(cursor_row, width)
+                .0
+                .iter()
+                .filter(|&&start| start <= self.position.column)
+                .count(),
+        };
+
+        // Walk up from the cursor only as far as a screenful reaches, so
+        // just the rows that can be shown are wrapped.
+        let mut top = top;
+
+        while top > self.scroll_offset.row {
+            let above = self.folds.visible(top - 1);
+
+            used += lines_of(above);
+
+            if used > height {
+                break;
+            }
+
+            top = above;
+        }
+
+        self.scroll_offset.row = self.scroll_offset.row.max(top);
+    }
+
+    fn scroll_column_into_view(&mut self, boundaries: Boundaries) {
+        let width = boundaries.width as usize;
+
+        let margin = self.config.side_scroll_off.min(width.saturating_sub(1) / 2);
+
+        let right_margin = margin.min(
+            self.document
+                .row_len(self.position.row)
+                .saturating_sub(self.position.column),
+        );
+
+        if self.position.column < self.scroll_offset.column + margin {
+            self.scroll_offset.column = self.position.column.saturating_sub(margin);
+
+            return;
+        }
+
+        // The offset is a column, but how many columns fit depends on how
+        // wide they are, so find the first one that leaves room for the
+        // cursor and its margin.
+        let right_edge = self.position.column + right_margin + 1;
+
+        let mut display = vec![0];
+
+        if let Some(row) = self.document.rows.get(self.position.row) {
+            for grapheme in row.content.graphemes(true).take(right_edge) {
+                let width = display[display.len() - 1];
+
+                display.push(width + grapheme_width(grapheme, width, self.config.tab_width));
+            }
+        }
+
+        while display.len() <= right_edge {
+            display.push(display[display.len() - 1] + 1);
+        }
+
+        if display[right_edge] > display[self.scroll_offset.column.min(right_edge)] + width {
+            self.scroll_offset.column = display
+                .partition_point(|cells| cells + width < display[right_edge])
+                .min(self.position.column);
+        }
+    }
+
+    pub fn goto_line(&mut self, boundaries: Boundaries, line: usize) {
+        self.jump_to_row(boundaries, line.saturating_sub(1));
+    }
+
+    fn jump_to_row(&mut self, boundaries: Boundaries, row: usize) {
+        self.position.row = row.min(self.document.rows.len().saturating_sub(1));
+
+        self.position.column = self
+            .position
+            .history
+            .column
+            .min(self.document.row_len(self.position.row));
+
+        self.scroll_into_view(boundaries);
+    }
+
+    pub fn undo(&mut self, boundaries: Boundaries) {
+        match self.document.undo() {
+            Some(row) => self.jump_to_row(boundaries, row),
+            None => self.set_message("Already at oldest change".to_owned()),
+        }
+    }
+
+    pub fn redo(&mut self, boundaries: Boundaries) {
+        match self.document.redo() {
+            Some(row) => self.jump_to_row(boundaries, row),
+            None => self.set_message("Already at newest change".to_owned()),
+        }
+    }
+
+    fn grapheme_at(&self, row: usize, column: usize) -> Option<&str> {
+        self.document.rows.get(row)?.grapheme_at(column)
+    }
+
+    pub fn insert(&mut self, boundaries: Boundaries, ch: char) -> Result<()> {
+        let auto_pairs = &self.config.auto_pairs;
+
+        let mut buf = [0; 4];
+        let ch_str: &str = ch.encode_utf8(&mut buf);
+
+        let next = self.grapheme_at(self.position.row, self.position.column);
+
+        if auto_pairs.enabled && auto_pairs.is_closing(ch) && next == Some(ch_str) {
+            if let Some(inserted) = self.recording.as_mut().and_then(LastChange::inserted_mut) {
+                inserted.push(ch);
+            }
+
+            return self.move_right(boundaries, 1);
+        }
+
+        let previous = self
+            .position
+            .column
+            .checked_sub(1)
+            .and_then(|column| self.grapheme_at(self.position.row, column));
+
+        let after_word = previous.is_some_and(|g| g.chars().all(char::is_alphanumeric));
+
+        let closing = auto_pairs
+            .closing(ch)
+            .filter(|close| auto_pairs.enabled && !(*close == ch && after_word));
+
+        if let Err(err) = self.document.insert(self.position, ch) {
+            self.set_message(err.to_string());
+
+            return Ok(());
+        }
+
+        if let Some(inserted) = self.recording.as_mut().and_then(LastChange::inserted_mut) {
+            inserted.push(ch);
+        }
+
+        self.move_right(boundaries, 1)?;
+
+        if let Some(close) = closing {
+            self.document.insert(self.position, close)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn overwrite(&mut self, boundaries: Boundaries, ch: char) -> Result<()> {
+        match self
+            .document
+            .replace_grapheme(self.position, ch.encode_utf8(&mut [0; 4]))
+        {
+            Ok(replaced) => self.replaced.push(replaced),
+
+            Err(err) => {
+                self.set_message(err.to_string());
+
+                return Ok(());
+            }
+        }
+
+        self.move_right(boundaries, 1)
+    }
+
+    pub fn overwrite_backward(&mut self, boundaries: Boundaries) -> Result<()> {
+        if self.position.column == 0 {
+            self.replaced.clear();
+
+            return self.move_left(boundaries, 1);
+        }
+
+        self.move_left(boundaries, 1)?;
+
+        match self.replaced.pop() {
+            Some(Some(original)) => {
+                self.document.replace_grapheme(self.position, &original)?;
+            }
+
+            Some(None) => self.document.delete(self.position),
+
+            None => (),
+        }
+
+        Ok(())
+    }
+
+    pub fn insert_tab(&mut self, boundaries: Boundaries) -> Result<()> {
+        if !self.config.expand_tabs {
+            return self.insert(boundaries, '\t');
+        }
+
+        let tab_width = self.config.tab_width.max(1);
+
+        for _ in 0..tab_width - self.position.column % tab_width {
+            self.insert(boundaries, ' ')?;
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_backward(&mut self, boundaries: Boundaries) -> Result<()> {
+        if self.position.row == 0 && self.position.column == 0 {
+            return Ok(());
+        }
+
+        if let Some(inserted) = self.recording.as_mut().and_then(LastChange::inserted_mut) {
+            if inserted.pop().is_none() {
+                self.recording = None;
+            }
+        }
+
+        let previous = self
+            .position
+            .column
+            .checked_sub(1)
+            .and_then(|column| self.grapheme_at(self.position.row, column))
+            .and_then(|g| g.chars().next());
+
+        let next = self.grapheme_at(self.position.row, self.position.column);
+
+        let is_empty_pair = self.config.auto_pairs.enabled
+            && previous
+                .and_then(|open| self.config.auto_pairs.closing(open))
+                .is_some_and(|close| next == Some(close.encode_utf8(&mut [0; 4])));
+
+        self.move_left(boundaries, 1)?;
+
+        self.document.delete(self.position);
+
+        if is_empty_pair {
+            self.document.delete(self.position);
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_word_before(&mut self, boundaries: Boundaries) -> Result<()> {
+        if self.position.column == 0 {
+            return self.delete_backward(boundaries);
+        }
+
+        let row = &self.document.rows[self.position.row];
+
+        let start = row.prev_word_start(self.position.column);
+
+        self.set_register(row.render(start, self.position.column));
+
+        self.move_left(boundaries, self.position.column - start)?;
+
+        for _ in 0..self.register.graphemes(true).count() {
+            self.document.delete(self.position);
+        }
+
+        Ok(())
+    }
+
+    pub fn apply_operator(
+        &mut self,
+        boundaries: Boundaries,
+        operator: Operator,
+        motion: Motion,
+        count: usize,
+    ) {
+        let change = LastChange::Operator {
+            operator,
+            motion,
+            count,
+            inserted: String::new(),
+        };
+
+        if operator == Operator::Change {
+            self.set_mode(EditorMode::Insert);
+
+            self.recording = Some(change);
+        } else {
+            self.last_change = Some(change);
+        }
+
+        let row = self.position.row;
+
+        let last_row = self.document.rows.len().saturating_sub(1);
+
+        let rows = match motion {
+            Motion::Down => Some((row, (row + count).min(last_row))),
+            Motion::Up => Some((row.saturating_sub(count), row)),
+            Motion::Line => Some((row, (row + count - 1).min(last_row))),
+            _ => None,
+        };
+
+        if let Some((start_row, end_row)) = rows {
+            let deleted = self.document.delete_rows(start_row, end_row);
+
+            self.set_register(deleted.join("\n") + "\n");
+
+            if operator == Operator::Change {
+                self.document.insert_row(start_row);
+            }
+
+            self.position.row = start_row.min(self.document.rows.len() - 1);
+
+            self.position.column = 0;
+        } else {
+            let current = &self.document.rows[row];
+
+            let column = self.position.column;
+
+            let (start, end) = match motion {
+                Motion::LineStart => (0, column),
+                Motion::LineEnd => (column, current.len()),
+                Motion::WordEnd => {
+                    let end =
+                        (1..count).fold(current.word_end(column), |end, _| current.word_end(end));
+
+                    (column, (end + 1).min(current.len()))
+                }
+                _ if operator == Operator::Change
+                    && current
+                        .grapheme_at(column)
+                        .is_some_and(|g| !g.trim().is_empty()) =>
+                {
+                    let end = (1..count).fold(current.current_word_end(column), |end, _| {
+                        current.word_end(end - 1) + 1
+                    });
+
+                    (column, end.min(current.len()))
+                }
+                _ => {
+                    let end = (0..count).fold(column, |end, _| current.next_word_start(end));
+
+                    (column, end)
+                }
+            };
+
+            if start < end {
+                let deleted = self.document.delete_range(
+                    Position {
+                        row,
+                        column: start,
+                        ..Default::default()
+                    },
+                    Position {
+                        row,
+                        column: end,
+                        ..Default::default()
+                    },
+                );
+
+                self.set_register(deleted);
+            }
+
+            self.position.column = start.min(self.document.row_len(row));
+        }
+
+        self.position.history.column = self.position.column;
+
+        self.scroll_into_view(boundaries);
+    }
+
+    pub fn start_insert(&mut self) {
+        self.set_mode(EditorMode::Insert);
+
+        self.recording = Some(LastChange::Insert {
+            inserted: String::new(),
+        });
+    }
+
+    pub fn delete_char(&mut self, boundaries: Boundaries, count: usize) {
+        let row = self.position.row;
+
+        let start = self.position.column;
+
+        let end = (start + count).min(self.document.row_len(row));
+
+        if start < end {
+            self.set_register(self.document.rows[row].render(start, end));
+
+            self.document.delete_columns(row, row, start, end - 1);
+        }
+
+        self.last_change = Some(LastChange::DeleteChar { count });
+
+        self.position.column = start.min(self.document.row_len(row));
+
+        self.position.history.column = self.position.column;
+
+        self.scroll_into_view(boundaries);
+    }
+
+    /// Replays the last change recorded by `x`, `d`/`c` with a motion, or an
+    /// `i` insert session. Only characters typed and deleted with Backspace
+    /// during the insert are recorded: moving the cursor with the arrow keys
+    /// while inserting is not replayed, and pasting or backspacing past the
+    /// start of the insert drops the recording.
+    pub fn repeat_last_change(
+        &mut self,
+        boundaries: Boundaries,
+        count: Option<usize>,
+    ) -> Result<()> {
+        let Some(change) = self.last_change.clone() else {
+            return Ok(());
+        };
+
+        match change {
+            LastChange::DeleteChar { count: last_count } => {
+                self.delete_char(boundaries, count.unwrap_or(last_count));
+            }
+
+            LastChange::Operator {
+                operator,
+                motion,
+                count: last_count,
+                inserted,
+            } => {
+                self.apply_operator(boundaries, operator, motion, count.unwrap_or(last_count));
+
+                if operator == Operator::Change {
+                    for ch in inserted.chars() {
+                        self.insert(boundaries, ch)?;
+                    }
+
+                    self.set_mode(EditorMode::Normal);
+                }
+            }
+
+            LastChange::Insert { inserted } => {
+                self.set_mode(EditorMode::Insert);
+
+                for _ in 0..count.unwrap_or(1) {
+                    for ch in inserted.chars() {
+                        self.insert(boundaries, ch)?;
+                    }
+                }
+
+                self.set_mode(EditorMode::Normal);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_to_line_start(&mut self, boundaries: Boundaries) -> Result<()> {
+        let deleted = self.document.delete_to_line_start(self.position);
+
+        if deleted.is_empty() {
+            return Ok(());
+        }
+
+        self.set_register(deleted);
+
+        self.move_left(boundaries, self.position.column)
+    }
+
+    pub fn copy_lines(&mut self, boundaries: Boundaries, (start, end): LineRange, below: usize) {
+        self.document.copy_rows(start, end, below);
+
+        self.jump_to_row(boundaries, below + end - start);
+    }
+
+    pub fn move_lines(&mut self, boundaries: Boundaries, (start, end): LineRange, below: usize) {
+        if (start..=end + 1).contains(&below) {
+            if below != start && below != end + 1 {
+                self.set_message("Cannot move a range of lines into itself".to_owned());
+            }
+
+            return;
+        }
+
+        self.document.move_rows(start, end, below);
+
+        let last = if below < start {
+            below + end - start
+        } else {
+            below - 1
+        };
+
+        self.jump_to_row(boundaries, last);
+    }
+
+    pub fn duplicate_line(&mut self, boundaries: Boundaries) {
+        let row = self.position.row;
+
+        self.copy_lines(boundaries, (row, row), row + 1);
+    }
+
+    fn set_register(&mut self, text: String) {
+        self.register = text;
+
+        self.pending_clipboard = self.config.clipboard;
+    }
+
+    pub fn put(&mut self, boundaries: Boundaries) {
+        if self.register.is_empty() {
+            return;
+        }
+
+        let mut at = self.position;
+
+        if self.document.row_len(at.row) > 0 {
+            at.column += 1;
+        }
+
+        match self.document.insert_str(at, &self.register) {
+            Ok(end) => {
+                self.position = end;
+
+                self.position.column = self.position.column.saturating_sub(1);
+
+                self.position.history.column = self.position.column;
+
+                self.scroll_into_view(boundaries);
+            }
+
+            Err(err) => self.set_message(err.to_string()),
+        }
+    }
+
+    pub fn set_quickfix(&mut self, entries: Vec<QuickfixEntry>) {
+        if entries.is_empty() {
+            self.set_message("No matches found".to_owned());
+        } else {
+            self.set_info(format!("{} matches", entries.len()));
+        }
+
+        self.quickfix = QuickfixList::new(entries);
+
+        self.overlay = self.quickfix.lines();
+    }
+
+    pub fn next_quickfix(&mut self, boundaries: Boundaries) {
+        match self.quickfix.select_next().cloned() {
+            Some(entry) => self.open_quickfix_entry(boundaries, entry),
+            None => self.set_message("No more items".to_owned()),
+        }
+    }
+
+    pub fn previous_quickfix(&mut self, boundaries: Boundaries) {
+        match self.quickfix.select_previous().cloned() {
+            Some(entry) => self.open_quickfix_entry(boundaries, entry),
+            None => self.set_message("No more items".to_owned()),
+        }
+    }
+
+    pub fn set_diagnostics(&mut self, mut diagnostics: Vec<Diagnostic>) {
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.row, diagnostic.col));
+
+        self.diagnostics = diagnostics;
+    }
+
+    pub fn diagnostic_at(&self, row: usize) -> Option<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.row == row)
+            .min_by_key(|diagnostic| diagnostic.severity)
+    }
+
+    pub fn diagnostic_count(&self, severity: DiagnosticSeverity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    pub fn next_diagnostic(&mut self, boundaries: Boundaries) {
+        let current = (self.position.row, self.position.column);
+
+        let next = self
+            .diagnostics
+            .iter()
+            .find(|diagnostic| (diagnostic.row, diagnostic.col) > current)
+            .or(self.diagnostics.first())
+            .cloned();
+
+        self.jump_to_diagnostic(boundaries, next);
+    }
+
+    pub fn previous_diagnostic(&mut self, boundaries: Boundaries) {
+        let current = (self.position.row, self.position.column);
+
+        let previous = self
+            .diagnostics
+            .iter()
+            .rev()
+            .find(|diagnostic| (diagnostic.row, diagnostic.col) < current)
+            .or(self.diagnostics.last())
+            .cloned();
+
+        self.jump_to_diagnostic(boundaries, previous);
+    }
+
+    fn jump_to_diagnostic(&mut self, boundaries: Boundaries, diagnostic: Option<Diagnostic>) {
+        let Some(diagnostic) = diagnostic else {
+            self.set_message("No diagnostics".to_owned());
+
+            return;
+        };
+
+        self.position.row = diagnostic
+            .row
+            .min(self.document.rows.len().saturating_sub(1));
+
+        self.position.column = diagnostic.col.min(self.document.row_len(self.position.row));
+
+        self.position.history.column = self.position.column;
+
+        self.scroll_into_view(boundaries);
+
+        let message = diagnostic.message.lines().next().unwrap_or_default();
+
+        self.set_message(message.to_owned());
+    }
+
+    pub fn open_file(&mut self, path: PathBuf) -> bool {
+        let is_open = self.document.path.as_ref() == Some(&path)
+            || self
+                .document
+                .canonical_path()
+                .is_some_and(|open_path| path.canonicalize().ok().as_deref() == Some(open_path));
+
+        if is_open {
+            return true;
+        }
+
+        self.load_file(path, false)
+    }
+
+    pub fn load_file(&mut self, path: PathBuf, force: bool) -> bool {
+        if self.document.modified && !force {
+            self.set_message("No write since last change (add ! to override)".to_owned());
+
+            return false;
+        }
+
+        match Document::open(Some(path.clone())) {
+            Ok(document) => {
+                self.close_help();
+
+                self.remember_position();
+
+                self.document.remove_swap();
+
+                self.document = document;
+
+                self.search_revision = None;
+
+                self.folds.clear();
+            }
+
+            Err(err) => {
+                self.set_message(format!("Could not open {}: {}", path.display(), err));
+
+                return false;
+            }
+        }
+
+        let config = self.file_config();
+
+        self.config.comment_string = config.comment_string;
+
+        self.config.filetype = config.filetype;
+
+        self.config.tab_width = config.tab_width;
+
+        self.config.expand_tabs = config.expand_tabs;
+
+        self.config.trim_on_save = config.trim_on_save;
+
+        self.config.format_on_save = config.format_on_save;
+
+        self.config.insert_final_newline = config.insert_final_newline;
+
+        self.apply_editorconfig();
+
+        self.report_indent();
+
+        self.report_lossy();
+
+        self.position = Position::default();
+
+        self.scroll_offset = Position::default();
+
+        self.restore_position();
+
+        self.record_recent();
+
+        self.git_diff = GitDiff::default();
+
+        self.diagnostics.clear();
+
+        self.pending_git_diff = true;
+
+        self.check_swap();
+
+        true
+    }
+
+    fn restore_position(&mut self) {
+        if !self.config.restore_position {
+            return;
+        }
+
+        let Some(position) = self.document.canonical_path().and_then(load_position) else {
+            return;
+        };
+
+        self.position.row = position.row.min(self.document.rows.len().saturating_sub(1));
+
+        self.position.column = position
+            .column
+            .min(self.document.row_len(self.position.row));
+
+        self.position.history.column = self.position.column;
+    }
+
+    fn record_recent(&self) {
+        if let Some(path) = self.document.canonical_path() {
+            let _ = record_recent(path);
+        }
+    }
+
+    pub fn remember_position(&self) {
+        if !self.config.restore_position {
+            return;
+        }
+
+        let (document, position) = match self.help_return.as_ref() {
+            Some(previous) => (&previous.document, previous.position),
+            None => (&self.document, self.position),
+        };
+
+        if let Some(path) = document.canonical_path() {
+            // Losing a position is not worth interrupting the user for.
+            let _ = save_position(path, position);
+        }
+    }
+
+    /// The `config.toml` settings for the current file, with the detected
+    /// indentation filling in what it leaves unset, and then a modeline's
+    /// file type and indentation taking precedence.
+    fn file_config(&self) -> EditorConfig {
+        let detected = self.document.detect_file_type();
+
+        let modeline_lines = self.user_config.editor_config(detected).modeline_lines;
+
+        let modeline = self.document.modeline(modeline_lines).unwrap_or_default();
+
+        let mut config = self
+            .user_config
+            .editor_config(modeline.filetype.unwrap_or(detected));
+
+        let set = |key| self.user_config.is_set(config.filetype, key);
+
+        if let Some(indent) = self.detected_indent(&config) {
+            if !set("expand_tabs") {
+                config.expand_tabs = indent.expand_tabs;
+            }
+
+            if let Some(width) = indent.width.filter(|_| !set("tab_width")) {
+                config.tab_width = width;
+            }
+        }
+
+        if let Some(width) = modeline.tab_width {
+            config.tab_width = width;
+        }
+
+        if let Some(expand_tabs) = modeline.expand_tabs {
+            config.expand_tabs = expand_tabs;
+        }
+
+        config
+    }
+
+    fn detected_indent(&self, config: &EditorConfig) -> Option<Indent> {
+        let set = |key| self.user_config.is_set(config.filetype, key);
+
+        if !config.detect_indent || set("expand_tabs") && set("tab_width") {
+            return None;
+        }
+
+        self.document.detect_indent(DETECT_INDENT_LINES)
+    }
+
+    fn report_indent(&mut self) {
+        if self.detected_indent(&self.config).is_none() {
+            return;
+        }
+
+        let indent = if self.config.expand_tabs {
+            format!("{} spaces", self.config.tab_width)
+        } else {
+            "tabs".to_owned()
+        };
+
+        self.set_info(format!("Indenting with {}", indent));
+    }
+
+    fn report_lossy(&mut self) {
+        if self.document.lossy {
+            self.set_message(format!(
+                "Some bytes aren't valid {} and were replaced",
+                self.document.encoding
+            ));
+        }
+    }
+
+    fn apply_editorconfig(&mut self) {
+        let Some(path) = self.document.path.as_deref() else {
+            return;
+        };
+
+        let settings = EditorConfigParser::find_and_parse(path);
+
+        if let Some(style) = settings.indent_style {
+            self.config.expand_tabs = style == IndentStyle::Space;
+        }
+
+        if let Some(size) = settings.indent_size {
+            self.config.tab_width = size;
+        }
+
+        if let Some(trim) = settings.trim_trailing_whitespace {
+            self.config.trim_on_save = trim;
+        }
+
+        if settings.insert_final_newline.is_some() {
+            self.config.insert_final_newline = settings.insert_final_newline;
+        }
+
+        if let Some(line_ending) = settings.end_of_line {
+            self.document.line_ending = line_ending;
+        }
+
+        let charset = settings.charset.as_deref();
+
+        let encoding = match charset {
+            Some("utf-8" | "utf-8-bom") => Some(Encoding::Utf8),
+            Some("latin1") => Some(Encoding::Latin1),
+            Some("utf-16le") => Some(Encoding::Utf16Le),
+            Some("utf-16be") => Some(Encoding::Utf16Be),
+            _ => None,
+        };
+
+        // The bytes were decoded with a guess, which a Latin-1 file that
+        // happens to be valid UTF-8 gets wrong.
+        if let Some(encoding) = encoding.filter(|encoding| *encoding != self.document.encoding) {
+            if let Err(err) = self.document.reload_as(encoding) {
+                self.set_message(format!("Could not read the file as {}: {}", encoding, err));
+            }
+        }
+
+        match charset {
+            Some("utf-8" | "latin1") => self.document.has_bom = false,
+            Some("utf-8-bom") => self.document.has_bom = true,
+            _ => (),
+        }
+    }
+
+    pub fn refuse_edit(&mut self, boundaries: Boundaries, position: Position) {
+        self.document.edit_refused = false;
+
+        self.position = position;
+
+        self.set_mode(EditorMode::Normal);
+
+        self.scroll_into_view(boundaries);
+
+        self.set_message("Buffer can't be edited".to_owned());
+    }
+
+    pub fn open_help(&mut self, text: &str) {
+        let mut document = Document::open(None).unwrap_or_default();
+
+        document.rows = text
+            .lines()
+            .map(|line| Row::from(line.to_owned()))
+            .collect();
+
+        document.readonly = true;
+
+        document.scratch = true;
+
+        let previous = HelpReturn {
+            document: std::mem::replace(&mut self.document, document),
+            position: std::mem::take(&mut self.position),
+            scroll_offset: std::mem::take(&mut self.scroll_offset),
+            git_diff: std::mem::take(&mut self.git_diff),
+            diagnostics: std::mem::take(&mut self.diagnostics),
+            folds: std::mem::take(&mut self.folds),
+        };
+
+        // Opening help from help keeps the original buffer to return to.
+        if self.help_return.is_none() {
+            self.help_return = Some(Box::new(previous));
+        }
+
+        self.search_revision = None;
+
+        self.set_mode(EditorMode::Normal);
+    }
+
+    pub fn search(&mut self, boundaries: Boundaries) {
+        let term = std::mem::take(&mut self.command_line);
+
+        // With the line taken, the preview puts back the cursor and the
+        // previous pattern, so the jump starts from where `/` was pressed.
+        self.preview_search(boundaries);
+
+        self.set_mode(EditorMode::Normal);
+
+        if !term.is_empty() {
+            self.search_history.retain(|entry| *entry != term);
+
+            self.search_history.push_front(term.clone());
+
+            self.search_history.truncate(SEARCH_HISTORY_LEN);
+
+            let options = SearchOptions {
+                ignore_case: self.search_ignores_case(&term),
+            };
+
+            match compile_search(&case_flags(&term).0, options) {
+                Ok(regex) => {
+                    self.search_regex = regex;
+
+                    self.search_options = options;
+                }
+
+                Err(err) => {
+                    self.set_message(regex_error(&err));
+
+                    return;
+                }
+            }
+
+            self.search_term = Some(term);
+
+            self.search_revision = None;
+        }
+
+        self.next_match(boundaries, true);
+    }
+
+    pub fn update_search(&mut self) {
+        if self.search_revision == Some(self.document.revision) {
+            return;
+        }
+
+        self.search_revision = Some(self.document.revision);
+
+        self.search_matches = match (self.search_regex.as_ref(), self.search_term.as_deref()) {
+            (Some(regex), _) => self.document.find_all_regex(regex),
+
+            (None, Some(term)) => {
+                let term = case_flags(term).0;
+
+                let len = term.graphemes(true).count();
+
+                self.document
+                    .find_all(&term, self.search_options)
+                    .into_iter()
+                    .map(|start| (start, start.column + len))
+                    .collect()
+            }
+
+            (None, None) => Vec::new(),
+        };
+    }
+
+    pub fn search_ignores_case(&self, term: &str) -> bool {
+        let (pattern, flag) = case_flags(term);
+
+        flag.unwrap_or_else(|| {
+            self.config.ignore_case
+                && !(self.config.smart_case && pattern.chars().any(char::is_uppercase))
+        })
+    }
+
+    pub fn search_matches_in_row(&self, row: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let first = self.search_matches.partition_point(|(m, _)| m.row < row);
+
+        self.search_matches[first..]
+            .iter()
+            .take_while(move |(m, _)| m.row == row)
+            .map(|(m, end)| (m.column, *end))
+    }
+
+    pub fn next_match(&mut self, boundaries: Boundaries, forward: bool) {
+        let Some(term) = self.search_term.clone() else {
+            self.set_message("No previous search pattern".to_owned());
+
+            return;
+        };
+
+        self.highlight_active = true;
+
+        self.update_search();
+
+        if self.search_matches.is_empty() {
+            self.set_message(format!("Pattern not found: {}", term));
+
+            return;
+        }
+
+        let (index, wrapped) = self.match_index(forward);
+
+        let (target, _) = self.search_matches[index];
+
+        self.jump_to(boundaries, target.row, target.column);
+
+        if wrapped {
+            self.set_info(if forward {
+                "Search hit BOTTOM, continuing at TOP".to_owned()
+            } else {
+                "Search hit TOP, continuing at BOTTOM".to_owned()
+            });
+        }
+    }
+
+    fn match_index(&self, forward: bool) -> (usize, bool) {
+        let cursor = (self.position.row, self.position.column);
+
+        let after = self
+            .search_matches
+            .partition_point(|(m, _)| (m.row, m.column) <= cursor);
+
+        let before = self
+            .search_matches
+            .partition_point(|(m, _)| (m.row, m.column) < cursor);
+
+        if forward {
+            match after < self.search_matches.len() {
+                true => (after, false),
+                false => (0, true),
+            }
+        } else {
+            match before.checked_sub(1) {
+                Some(index) => (index, false),
+                None => (self.search_matches.len() - 1, true),
+            }
+        }
+    }
+
+    pub fn preview_search(&mut self, boundaries: Boundaries) {
+        let Some(origin) = self.search_origin.as_ref() else {
+            return;
+        };
+
+        self.position = origin.position;
+
+        self.scroll_offset = origin.scroll_offset;
+
+        self.search_failed = false;
+
+        if self.command_line.is_empty() {
+            self.restore_search_origin();
+
+            return;
+        }
+
+        let options = SearchOptions {
+            ignore_case: self.search_ignores_case(&self.command_line),
+        };
+
+        // A pattern being typed is often not a valid regex yet.
+        let Ok(regex) = compile_search(&case_flags(&self.command_line).0, options) else {
+            self.search_failed = true;
+
+            return;
+        };
+
+        self.search_term = Some(self.command_line.clone());
+
+        self.search_regex = regex;
+
+        self.search_options = options;
+
+        self.search_revision = None;
+
+        self.highlight_active = true;
+
+        self.update_search();
+
+        if self.search_matches.is_empty() {
+            self.search_failed = true;
+
+            return;
+        }
+
+        let (target, _) = self.search_matches[self.match_index(true).0];
+
+        // Folds open only once the search is committed, not for every match
+        // the preview passes on the way.
+        self.move_to(boundaries, target.row, target.column);
+    }
+
+    pub fn browse_search_history(&mut self, boundaries: Boundaries, older: bool) {
+        let index = match (self.search_history_index, older) {
+            (None, true) => Some(0),
+            (None, false) => return,
+            (Some(index), true) => {
+                Some((index + 1).min(self.search_history.len().saturating_sub(1)))
+            }
+            (Some(index), false) => index.checked_sub(1),
+        };
+
+        let Some(entry) = index.and_then(|index| self.search_history.get(index)) else {
+            if self.search_history_index.take().is_some() {
+                self.command_line = std::mem::take(&mut self.search_draft);
+
+                self.preview_search(boundaries);
+            }
+
+            return;
+        };
+
+        if self.search_history_index.is_none() {
+            self.search_draft = self.command_line.clone();
+        }
+
+        self.command_line = entry.clone();
+
+        self.search_history_index = index;
+
+        self.preview_search(boundaries);
+    }
+
+    pub fn cancel_search(&mut self) {
+        if let Some(origin) = self.search_origin.as_ref() {
+            self.position = origin.position;
+
+            self.scroll_offset = origin.scroll_offset;
+
+            self.restore_search_origin();
+        }
+
+        self.set_mode(EditorMode::Normal);
+    }
+
+    fn restore_search_origin(&mut self) {
+        let Some(origin) = self.search_origin.as_ref() else {
+            return;
+        };
+
+        self.search_term = origin.term.clone();
+
+        self.search_regex = origin.regex.clone();
+
+        self.search_options = origin.options;
+
+        self.highlight_active = origin.highlight_active;
+
+        self.search_revision = None;
+    }
+
+    /// Runs `/pattern/replacement/[g]` on `start..=end`. The pattern is
+    /// always a regular expression so the replacement can use `$1`.
+    fn substitute(&mut self, boundaries: Boundaries, start: usize, end: usize, args: &str) {
+        let Some(delimiter) = args.chars().next().filter(|ch| !ch.is_alphanumeric()) else {
+            self.set_message("Usage: :s/pattern/replacement/[g]".to_owned());
+
+            return;
+        };
+
+        let parts = split_unescaped(&args[delimiter.len_utf8()..], delimiter);
+
+        let (pattern, replacement, flags) = match parts.as_slice() {
+            [pattern] => (pattern.as_str(), "", ""),
+            [pattern, replacement] => (pattern.as_str(), replacement.as_str(), ""),
+            [pattern, replacement, flags] => {
+                (pattern.as_str(), replacement.as_str(), flags.as_str())
+            }
+            _ => {
+                self.set_message(format!("Trailing characters: {}", args));
+
+                return;
+            }
+        };
+
+        if let Some(flag) = flags.chars().find(|flag| *flag != 'g') {
+            self.set_message(format!("Unknown flag: {}", flag));
+
+            return;
+        }
+
+        let regex = match Regex::new(pattern.strip_prefix("\\v").unwrap_or(pattern)) {
+            Ok(regex) => regex,
+
+            Err(err) => {
+                self.set_message(regex_error(&err));
+
+                return;
+            }
+        };
+
+        let (replacements, rows) =
+            self.document
+                .substitute(start, end, &regex, replacement, flags.contains('g'));
+
+        if replacements == 0 {
+            self.set_message(format!("Pattern not found: {}", pattern));
+
+            return;
+        }
+
+        self.jump_to_row(boundaries, self.position.row);
+
+        self.set_info(format!(
+            "{} substitution(s) on {} line(s)",
+            replacements, rows
+        ));
+    }
+
+    pub fn toggle_fold(&mut self, boundaries: Boundaries) {
+        if self.mode == EditorMode::Normal && self.folds.toggle(self.position.row) {
+            self.leave_folds(boundaries);
+
+            return;
+        }
+
+        self.create_fold(boundaries);
+    }
+
+    pub fn create_fold(&mut self, boundaries: Boundaries) {
+        let rows = match self.selected_range() {
+            Some((start, end)) => Some((start.row, end.row)),
+            None => self.indent_block(self.position.row),
+        };
+
+        self.set_mode(EditorMode::Normal);
+
+        match rows {
+            Some((start, end)) if start < end => {
+                self.folds
+                    .adjust(self.position.row, self.document.rows.len());
+
+                self.folds.add(start, end);
+
+                self.leave_folds(boundaries);
+            }
+
+            _ => self.set_message("Nothing to fold".to_owned()),
+        }
+    }
+
+    pub fn delete_folds(&mut self) {
+        self.folds.clear();
+    }
+
+    fn indent_block(&self, row: usize) -> Option<(usize, usize)> {
+        let rows = &self.document.rows;
+
+        let indent = |row: usize| {
+            let content = &rows[row].content;
+
+            (!content.trim().is_empty()).then(|| content.len() - content.trim_start().len())
+        };
+
+        let level = indent(row)?;
+
+        let next = (row + 1..rows.len()).find_map(indent);
+
+        let (start, level) = match next {
+            Some(next) if next > level => (row, next),
+            _ if level > 0 => {
+                let start = (0..row)
+                    .rev()
+                    .find(|&above| indent(above).is_some_and(|i| i < level))?;
+
+                (start, level)
+            }
+            _ => return None,
+        };
+
+        let mut end = start;
+
+        for below in start + 1..rows.len() {
+            match indent(below) {
+                Some(indent) if indent < level => break,
+                Some(_) => end = below,
+                None => (),
+            }
+        }
+
+        Some((start, end))
+    }
+
+    pub fn sync_folds(&mut self, boundaries: Boundaries, edit_row: usize) {
+        if self.folds.is_empty() {
+            return;
+        }
+
+        self.folds.adjust(edit_row, self.document.rows.len());
+
+        self.leave_folds(boundaries);
+    }
+
+    fn leave_folds(&mut self, boundaries: Boundaries) {
+        if self.folds.is_hidden(self.position.row) {
+            self.position.row = self.folds.visible(self.position.row);
+
+            self.position.column = 0;
+        }
+
+        self.scroll_into_view(boundaries);
+    }
+
+    pub fn split(&mut self, vertical: bool) {
+        if self.split.is_some() {
+            self.set_message("Only one split is supported".to_owned());
+
+            return;
+        }
+
+        self.split = Some(Split {
+            vertical,
+            focus_first: true,
+            position: self.position,
+            scroll_offset: self.scroll_offset,
+        });
+    }
+
+    pub fn focus_other_split(&mut self, boundaries: Boundaries) {
+        let Some(split) = self.split.as_mut() else {
+            return;
+        };
+
+        split.focus_first = !split.focus_first;
+
+        std::mem::swap(&mut self.position, &mut split.position);
+
+        std::mem::swap(&mut self.scroll_offset, &mut split.scroll_offset);
+
+        self.position.row = self
+            .position
+            .row
+            .min(self.document.rows.len().saturating_sub(1));
+
+        self.position.column = self
+            .position
+            .column
+            .min(self.document.row_len(self.position.row));
+
+        self.scroll_into_view(boundaries);
+    }
+
+    pub fn close_split(&mut self, boundaries: Boundaries, close_focused: bool) {
+        if self.split.is_none() {
+            self.set_message("There is only one window".to_owned());
+
+            return;
+        }
+
+        if close_focused {
+            self.focus_other_split(boundaries);
+        }
+
+        self.split = None;
+    }
+
+    pub fn is_help(&self) -> bool {
+        self.help_return.is_some()
+    }
+
+    pub fn close_help(&mut self) {
+        let Some(previous) = self.help_return.take() else {
+            return;
+        };
+
+        self.document = previous.document;
+
+        self.position = previous.position;
+
+        self.scroll_offset = previous.scroll_offset;
+
+        self.git_diff = previous.git_diff;
+
+        self.diagnostics = previous.diagnostics;
+
+        self.folds = previous.folds;
+
+        self.search_revision = None;
+    }
+
+    pub fn jump_to_definitions(&mut self, boundaries: Boundaries, entries: Vec<QuickfixEntry>) {
+        match entries.len() {
+            0 => self.set_message("No definition found".to_owned()),
+
+            1 => {
+                self.jump_to_entry(boundaries, &entries[0]);
+            }
+
+            _ => {
+                self.set_quickfix(entries);
+
+                self.next_quickfix(boundaries);
+            }
+        }
+    }
+
+    fn jump_to_entry(&mut self, boundaries: Boundaries, entry: &QuickfixEntry) -> bool {
+        if !self.open_file(entry.path.clone()) {
+            return false;
+        }
+
+        self.jump_to(boundaries, entry.line, entry.col);
+
+        true
+    }
+
+    pub fn jump_to(&mut self, boundaries: Boundaries, row: usize, column: usize) {
+        self.folds
+            .open_at(row.min(self.document.rows.len().saturating_sub(1)));
+
+        self.move_to(boundaries, row, column);
+    }
+
+    /// Moves the cursor like `jump_to`, leaving the fold it lands in closed.
+    fn move_to(&mut self, boundaries: Boundaries, row: usize, column: usize) {
+        self.position.row = row.min(self.document.rows.len().saturating_sub(1));
+
+        self.position.column = column.min(self.document.row_len(self.position.row));
+
+        self.position.history.column = self.position.column;
+
+        self.scroll_into_view(boundaries);
+    }
+
+    fn open_quickfix_entry(&mut self, boundaries: Boundaries, entry: QuickfixEntry) {
+        if !self.jump_to_entry(boundaries, &entry) {
+            return;
+        }
+
+        self.set_message(format!(
+            "({} of {}) {}",
+            self.quickfix.current.map_or(0, |current| current + 1),
+            self.quickfix.len(),
+            entry.text.trim()
+        ));
+    }
+
+    pub fn show_stats(&mut self) {
+        let rows = &self.document.rows;
+
+        let words_in = |content: &str| content.split_whitespace().count();
+
+        let total_chars: usize = rows.iter().map(|r| r.len()).sum();
+
+        let total_words: usize = rows.iter().map(|r| words_in(&r.content)).sum();
+
+        let chars_before: usize = rows[..self.position.row].iter().map(|r| r.len()).sum();
+
+        let words_before: usize = rows[..self.position.row]
+            .iter()
+            .map(|r| words_in(&r.content))
+            .sum();
+
+        let current_row = &rows[self.position.row];
+
+        let current_word =
+            words_before + words_in(&current_row.render(0, self.position.column + 1));
+
+        self.set_message(format!(
+            "Line {} of {}; Col {} of {}; Word {} of {}; Char {} of {}",
+            self.position.row + 1,
+            rows.len(),
+            self.position.column + 1,
+            current_row.len(),
+            current_word,
+            total_words,
+            (chars_before + self.position.column + 1).min(total_chars),
+            total_chars
+        ));
+    }
+
+    pub fn toggle_comment(&mut self, start_row: usize, end_row: usize) {
+        let Some(comment) = self.config.comment_string.as_deref() else {
+            self.set_message("No comment string for this file".to_owned());
+
+            return;
+        };
+
+        let end_row = end_row.min(self.document.rows.len().saturating_sub(1));
+
+        self.document
+            .toggle_comment_range(start_row, end_row, comment);
+
+        self.position.column = self
+            .position
+            .column
+            .min(self.document.row_len(self.position.row));
+    }
+
+    pub fn increment(&mut self, boundaries: Boundaries, delta: i64) {
+        if let Some(end) = self.document.increment_at(self.position, delta) {
+            self.position = end;
+
+            self.position.history.column = self.position.column;
+
+            self.scroll_into_view(boundaries);
+        }
+    }
+
+    pub fn complete(&mut self, boundaries: Boundaries, text: &str) {
+        self.recording = None;
+
+        let mut start = self.position;
+
+        start.column = self
+            .document
+            .rows
+            .get(start.row)
+            .map_or(start.column, |row| row.partial_word_start(start.column));
+
+        if start.column < self.position.column {
+            self.document.delete_columns(
+                start.row,
+                start.row,
+                start.column,
+                self.position.column - 1,
+            );
+        }
+
+        match self.document.insert_str(start, text) {
+            Ok(end) => {
+                self.position = end;
+
+                self.position.history.column = self.position.column;
+            }
+
+            Err(err) => self.set_message(err.to_string()),
+        }
+
+        self.scroll_into_view(boundaries);
+    }
+
+    pub fn paste(&mut self, boundaries: Boundaries, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+        self.recording = None;
+
+        self.document.history.end_group();
+
+        match self.document.insert_str(self.position, &text) {
+            Ok(end) => {
+                self.position = end;
+
+                self.position.history.column = self.position.column;
+            }
+
+            Err(err) => self.set_message(err.to_string()),
+        }
+
+        if self.mode == EditorMode::Insert {
+            self.document.history.begin_group();
+        }
+
+        self.scroll_into_view(boundaries);
+    }
+
+    fn parse_address<'a>(&self, text: &'a str) -> Option<(usize, &'a str)> {
+        if let Some(rest) = text.strip_prefix('.') {
+            return Some((self.position.row + 1, rest));
+        }
+
+        if let Some(rest) = text.strip_prefix('$') {
+            return Some((self.document.rows.len(), rest));
+        }
+
+        let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+        let line = text[..digits].parse().ok()?;
+
+        Some((line, &text[digits..]))
+    }
+
+    fn parse_range<'a>(
+        &self,
+        command_line: &'a str,
+    ) -> Result<(Option<LineRange>, &'a str), String> {
+        let last = self.document.rows.len();
+
+        if let Some(rest) = command_line.strip_prefix('%') {
+            return Ok((Some((0, last - 1)), rest));
+        }
+
+        let Some((start, rest)) = self.parse_address(command_line) else {
+            return Ok((None, command_line));
+        };
+
+        let (end, rest) = match rest.strip_prefix(',') {
+            Some(rest) => self.parse_address(rest).ok_or("Invalid range")?,
+            None => (start, rest),
+        };
+
+        if start == 0 || end == 0 || start > last || end > last {
+            return Err("Invalid range".to_owned());
+        }
+
+        if start > end {
+            return Err("Backwards range given".to_owned());
+        }
+
+        Ok((Some((start - 1, end - 1)), rest))
+    }
+
+    pub fn execute_command(&mut self, boundaries: Boundaries) {
+        let command_line = std::mem::take(&mut self.command_line);
+
+        let selected_rows = self
+            .selected_range()
+            .map(|(start, end)| (start.row, end.row));
+
+        self.set_mode(EditorMode::Normal);
+
+        let (range, command_line) = match self.parse_range(command_line.trim()) {
+            Ok(parsed) => parsed,
+
+            Err(err) => {
+                self.set_message(err);
+
+                return;
+            }
+        };
+
+        let selected_rows = range.or(selected_rows);
+
+        let command_line = command_line.trim();
+
+        let name_len = command_line.len()
+            - command_line
+                .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+                .len();
+
+        let name_len = name_len + command_line[name_len..].starts_with('!') as usize;
+
+        let (command, args) = command_line.split_at(name_len);
+
+        let args = args.trim();
+
+        match command {
+            "" => {
+                if let Some((_, end)) = range {
+                    self.jump_to_row(boundaries, end);
+                }
+            }
+
+            "set" => {
+                for option in args.split_whitespace() {
+                    match option {
+                        "bom" | "nobom" => {
+                            let has_bom = option == "bom";
+
+                            if self.document.has_bom != has_bom {
+                                self.document.has_bom = has_bom;
+
+                                self.document.modified = true;
+                            }
+                        }
+
+                        _ if option.starts_with("encoding=")
+                            || option.starts_with("fileencoding=")
+                            || option.starts_with("fenc=") =>
+                        {
+                            match option[option.find('=').unwrap_or(0) + 1..].parse() {
+                                Ok(encoding) if encoding != self.document.encoding => {
+                                    self.document.encoding = encoding;
+
+                                    self.document.modified = true;
+                                }
+
+                                Ok(_) => (),
+
+                                Err(err) => self.set_message(err.to_string()),
+                            }
+                        }
+
+                        _ => {
+                            if let Err(err) = self.config.set(option) {
+                                self.set_message(err.to_string());
+                            }
+                        }
+                    }
+                }
+
+                self.scroll_into_view(boundaries);
+            }
+
+            "e" | "e!" | "edit" | "edit!" => {
+                let force = command.ends_with('!');
+
+                let path = match args.trim() {
+                    "" => self.document.path.clone(),
+                    path => Some(PathBuf::from(path)),
+                };
+
+                match path {
+                    Some(path) => {
+                        if self.load_file(path, force) {
+                            self.scroll_into_view(boundaries);
+                        }
+                    }
+
+                    None => self.set_message("No file name".to_owned()),
+                }
+            }
+
+            "w" | "w!" | "write" | "write!" => {
+                let path = (!args.is_empty()).then(|| PathBuf::from(args));
+
+                self.save_to(path, command.ends_with('!'));
+            }
+
+            "t" | "copy" | "m" | "move" => {
+                let rows = selected_rows.unwrap_or((self.position.row, self.position.row));
+
+                match self.parse_address(args.trim()) {
+                    Some((line, "")) if line <= self.document.rows.len() => {
+                        if command.starts_with('m') {
+                            self.move_lines(boundaries, rows, line);
+                        } else {
+                            self.copy_lines(boundaries, rows, line);
+                        }
+                    }
+
+                    _ => self.set_message(format!("Usage: :{} <line>", command)),
+                }
+            }
+
+            "sort" | "sort!" => {
+                let reverse = command == "sort!";
+
+                let unique = args.trim() == "u";
+
+                match selected_rows {
+                    Some((start, end)) => self.document.sort_range(start, end, reverse, unique),
+                    None => self.document.sort_rows(reverse, unique),
+                }
+
+                self.jump_to_row(boundaries, self.position.row);
+            }
+
+            "grep" if args.is_empty() => {
+                self.set_message("Usage: :grep <pattern>".to_owned());
+            }
+
+            "grep" => {
+                self.set_message(format!("Searching for {}...", args));
+
+                self.pending_grep = Some(args.to_owned());
+            }
+
+            "cnext" | "cn" => self.next_quickfix(boundaries),
+
+            "cprevious" | "cp" => self.previous_quickfix(boundaries),
+
+            "messages" => {
+                if self.messages.is_empty() {
+                    self.set_message("No messages".to_owned());
+                } else {
+                    self.overlay = self.messages.lines();
+                }
+            }
+
+            "nohlsearch" | "noh" => self.highlight_active = false,
+
+            "history" | "his" => match args {
+                "" | "/" if self.search_history.is_empty() => {
+                    self.set_message("No search history".to_owned());
+                }
+
+                "" | "/" => {
+                    self.overlay = self
+                        .search_history
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, entry)| format!("{:>3}  {}", i + 1, entry))
+                        .collect();
+                }
+
+                _ => self.set_message(format!("Unknown history: {}", args)),
+            },
+
+            "recent" => {
+                let files = load_recent();
+
+                if files.is_empty() {
+                    self.set_message("No recent files".to_owned());
+                } else {
+                    self.recent_files = Some(RecentFiles::new(files));
+                }
+            }
+
+            "help" | "h" => self.pending_help = true,
+
+            "bnext" | "bn" => self.pending_buffer = Some(BufferSwitch::Next),
+
+            "bprevious" | "bp" => self.pending_buffer = Some(BufferSwitch::Previous),
+
+            "split" | "sp" => self.split(false),
+
+            "vsplit" | "vs" => self.split(true),
+
+            "only" | "on" => self.close_split(boundaries, false),
+
+            "close" | "clo" => self.close_split(boundaries, true),
+
+            "stats" => self.show_stats(),
+
+            "s" | "substitute" => {
+                let (start, end) = selected_rows.unwrap_or((self.position.row, self.position.row));
+
+                self.substitute(boundaries, start, end, &command_line[command.len()..]);
+            }
+
+            "retab" | "retab!" => {
+                let (start, end) =
+                    selected_rows.unwrap_or((0, self.document.rows.len().saturating_sub(1)));
+
+                self.document
+                    .retab(start, end, self.config.tab_width, command == "retab!");
+
+                self.jump_to_row(boundaries, self.position.row);
+            }
+
+            "trim" => {
+                self.document.trim_trailing_whitespace();
+
+                self.jump_to_row(boundaries, self.position.row);
+            }
+
+            command => {
+                self.set_message(format!("Not an editor command: {}", command));
+            }
+        }
+    }
+
+    pub fn save(&mut self, force: bool) {
+        self.save_to(None, force);
+    }
+
+    fn save_to(&mut self, path: Option<PathBuf>, force: bool) {
+        if self.document.readonly && !force {
+            self.set_message("File is read-only (add ! to override)".to_owned());
+
+            return;
+        }
+
+        if self.document.path.is_none() && path.is_none() {
+            self.set_message("No file name (use :w <file>)".to_owned());
+
+            return;
+        }
+
+        // Checked before trimming and formatting so that a refused save
+        // leaves the buffer as it was.
+        if !force && self.document.save_conflicts(path.as_deref()) {
+            self.set_message(format!(
+                "Could not save the document: {}",
+                DocumentError::ChangedOnDisk
+            ));
+
+            return;
+        }
+
+        if self.config.trim_on_save && !self.document.scratch {
+            self.document.trim_trailing_whitespace();
+
+            self.position.column = self
+                .position
+                .column
+                .min(self.document.row_len(self.position.row));
+        }
+
+        let file = path.clone().or_else(|| self.document.path.clone());
+
+        let format_error = match self.config.format_on_save && !self.document.scratch {
+            true => file.and_then(|file| self.format(&file).err()),
+            false => None,
+        };
+
+        let final_newline = self.config.insert_final_newline;
+
+        let result = match path {
+            Some(path) => self.document.save_as(path, force, final_newline),
+            None => self.document.save(force, final_newline),
+        };
+
+        match result {
+            Ok(n) => {
+                self.pending_git_diff = true;
+
+                self.saved = true;
+
+                self.remember_position();
+
+                self.set_info(format!(
+                    "'{}' saved, {}L {}B",
+                    self.document.path.as_ref().unwrap().display(),
+                    self.document.rows.len(),
+                    n
+                ));
+
+                if let Some(err) = format_error {
+                    self.set_message(format!("Saved without formatting: {:#}", err));
+                }
+            }
+
+            Err(err) => {
+                self.set_message(format!("Could not save the document: {}", err));
+            }
+        }
+    }
+
+    fn format(&mut self, file: &Path) -> Result<()> {
+        let Some(formatter) = self.config.filetype.formatter() else {
+            return Ok(());
+        };
+
+        let file = file.to_string_lossy();
+
+        let mut words = formatter
+            .split_whitespace()
+            .map(|word| word.replace("{file}", &file));
+
+        let program = words.next().unwrap_or_default();
+
+        let mut child = Command::new(&program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("could not start {}", program))?;
+
+        // Writing from another thread keeps a formatter that answers before
+        // reading everything from blocking on a full pipe.
+        let mut stdin = child.stdin.take().context("no stdin")?;
+
+        let text = self.document.text();
+
+        let writer = thread::spawn(move || stdin.write_all(text.as_bytes()));
+
+        let stdout = read_in_background(child.stdout.take().context("no stdout")?);
+
+        let stderr = read_in_background(child.stderr.take().context("no stderr")?);
+
+        // The formatter runs on the UI thread, so one that hangs is killed
+        // rather than waited for.
+        let deadline = Instant::now() + FORMAT_TIMEOUT;
+
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+
+                let _ = child.wait();
+
+                bail!("{} timed out", program);
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let _ = writer.join();
+
+        let stdout = stdout.join().unwrap_or_default();
+
+        if !status.success() {
+            let stderr = stderr.join().unwrap_or_default();
+
+            let stderr = String::from_utf8_lossy(&stderr);
+
+            bail!(
+                "{} failed: {}",
+                program,
+                stderr.lines().next().unwrap_or_default()
+            );
+        }
+
+        let formatted = String::from_utf8(stdout)
+            .with_context(|| format!("{} wrote invalid UTF-8", program))?;
+
+        self.document.replace_text(&formatted);
+
+        self.position.row = self.position.row.min(self.document.rows.len() - 1);
+
+        self.position.column = self
+            .position
+            .column
+            .min(self.document.row_len(self.position.row));
+
+        Ok(())
+    }
+}
+
+fn read_in_background(mut pipe: impl io::Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+
+        let _ = pipe.read_to_end(&mut bytes);
+
+        bytes
+    })
+}
+
+/// Compiles a search pattern that starts with `\v` or contains regex
+/// metacharacters; anything else is searched for literally.
+fn compile_search(term: &str, options: SearchOptions) -> Result<Option<Regex>, regex::Error> {
+    let pattern = match term.strip_prefix("\\v") {
+        Some(pattern) => pattern,
+        None if term.contains(|ch| ".*+?()[]{}|^$\\".contains(ch)) => term,
+        None => return Ok(None),
+    };
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .map(Some)
+}
+
+fn case_flags(term: &str) -> (String, Option<bool>) {
+    let mut pattern = String::with_capacity(term.len());
+
+    let mut flag = None;
+
+    let mut chars = term.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            pattern.push(ch);
+
+            continue;
+        }
+
+        match chars.next() {
+            Some('c') => flag = Some(true),
+            Some('C') => flag = Some(false),
+            Some(next) => {
+                pattern.push(ch);
+
+                pattern.push(next);
+            }
+            None => pattern.push(ch),
+        }
+    }
+
+    (pattern, flag)
+}
+
+fn regex_error(err: &regex::Error) -> String {
+    let message = err.to_string();
+
+    let reason = message.lines().last().unwrap_or_default();
+
+    format!("Invalid pattern: {}", reason.trim_start_matches("error: "))
+}
+
+fn split_unescaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\\' && chars.peek() == Some(&delimiter) {
+            parts.last_mut().unwrap().push(delimiter);
+
+            chars.next();
+        } else if ch == delimiter {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(ch);
+        }
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(lines: &[&str]) -> Editor {
+        let mut document = Document::open(None).unwrap();
+
+        document.rows = lines
+            .iter()
+            .map(|line| Row::from(line.to_string()))
+            .collect();
+
+        Editor {
+            document,
+            ..Default::default()
+        }
+    }
+
+    fn numbered(count: usize) -> Editor {
+        let lines: Vec<String> = (0..count).map(|i| i.to_string()).collect();
+
+        editor(&lines.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn held_up_stops_at_the_first_row() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let mut editor = numbered(50);
+
+        editor.position.row = 3;
+
+        editor.scroll_offset.row = 2;
+
+        for offset in [1, 5, 20, 20] {
+            editor.move_up(boundaries, offset).unwrap();
+        }
+
+        assert_eq!(editor.position.row, 0);
+        assert_eq!(editor.scroll_offset.row, 0);
+    }
+
+    #[test]
+    fn held_down_reaches_the_last_row() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let mut editor = numbered(50);
+
+        editor.position.row = 45;
+
+        editor.scroll_offset.row = 40;
+
+        editor.move_down(boundaries, 20).unwrap();
+
+        assert_eq!(editor.position.row, 49);
+        assert!(editor.scroll_offset.row <= 49);
+        assert!(editor.scroll_offset.row + 10 > 49);
+
+        editor.move_down(boundaries, 20).unwrap();
+
+        assert_eq!(editor.position.row, 49);
+    }
+
+    #[test]
+    fn display_lines_step_through_a_wrapped_row() {
+        let boundaries = Boundaries::new(10, 10);
+
+        let mut editor = editor(&["abcdefghijklmnopqrstuvwxy", "short"]);
+
+        editor.config.wrap = true;
+
+        editor.position.column = 3;
+
+        editor.move_display_lines(boundaries, true, 1).unwrap();
+
+        assert_eq!((editor.position.row, editor.position.column), (0, 13));
+        assert_eq!(editor.visual_cursor(boundaries), (1, 3));
+
+        editor.move_display_lines(boundaries, true, 1).unwrap();
+
+        assert_eq!((editor.position.row, editor.position.column), (0, 23));
+        assert_eq!(editor.visual_cursor(boundaries), (2, 3));
+
+        editor.move_display_lines(boundaries, true, 1).unwrap();
+
+        assert_eq!((editor.position.row, editor.position.column), (1, 3));
+
+        editor.move_display_lines(boundaries, false, 2).unwrap();
+
+        assert_eq!((editor.position.row, editor.position.column), (0, 13));
+    }
+
+    #[test]
+    fn display_line_counts_stop_at_the_ends_without_wrap() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let mut editor = numbered(5);
+
+        editor.position.row = 2;
+
+        editor.move_display_lines(boundaries, false, 5).unwrap();
+
+        assert_eq!(editor.position.row, 0);
+
+        editor.position.row = 3;
+
+        editor.move_display_lines(boundaries, true, 5).unwrap();
+
+        assert_eq!(editor.position.row, 4);
+    }
+
+    #[test]
+    fn help_buffers_refuse_edits() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let mut editor = editor(&["help text"]);
+
+        editor.open_help("help text\nmore");
+
+        editor.set_mode(EditorMode::Insert);
+
+        assert!(editor.mode == EditorMode::Normal);
+
+        editor.delete_char(boundaries, 3);
+
+        editor.put(boundaries);
+
+        editor.undo(boundaries);
+
+        assert!(editor.document.edit_refused);
+
+        editor.refuse_edit(boundaries, Position::default());
+
+        assert_eq!(editor.document.rows[0].content, "help text");
+        assert!(!editor.document.modified);
+        assert!(!editor.document.edit_refused);
+        assert_eq!(editor.status.to_string(), "Buffer can't be edited");
+    }
+
+    #[test]
+    fn read_only_buffers_can_be_edited_but_not_saved() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let mut editor = editor(&["text"]);
+
+        editor.document.readonly = true;
+
+        editor.set_mode(EditorMode::Insert);
+
+        assert!(editor.mode == EditorMode::Insert);
+
+        editor.insert(boundaries, 'a').unwrap();
+
+        editor.set_mode(EditorMode::Normal);
+
+        assert_eq!(editor.document.rows[0].content, "atext");
+        assert!(!editor.document.edit_refused);
+
+        editor.save(false);
+
+        assert!(editor.document.modified);
+        assert_eq!(
+            editor.status.to_string(),
+            "File is read-only (add ! to override)"
+        );
+    }
+
+    #[test]
+    fn wrapped_scroll_keeps_the_cursor_on_the_last_screen_line() {
+        let boundaries = Boundaries::new(10, 10);
+
+        let row = "abcdefghijklmnopqrstuvwxy";
+
+        let mut editor = editor(&vec![row; 1000]);
+
+        editor.config.wrap = true;
+
+        editor.position.row = 999;
+
+        editor.scroll_into_view(boundaries);
+
+        assert_eq!(editor.scroll_offset.row, 996);
+        assert_eq!(editor.visual_cursor(boundaries), (9, 0));
+
+        editor.position.column = 20;
+
+        editor.scroll_into_view(boundaries);
+
+        assert_eq!(editor.scroll_offset.row, 997);
+        assert_eq!(editor.visual_cursor(boundaries), (8, 0));
+    }
+
+    #[test]
+    fn only_a_committed_search_opens_folds() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let mut editor = numbered(30);
+
+        editor.folds.adjust(0, 30);
+
+        editor.folds.add(10, 20);
+
+        editor.set_mode(EditorMode::Search);
+
+        editor.command_line = "15".to_owned();
+
+        editor.preview_search(boundaries);
+
+        assert_eq!(editor.position.row, 15);
+        assert!(editor.folds.is_hidden(15));
+
+        editor.search(boundaries);
+
+        assert_eq!(editor.position.row, 15);
+        assert!(!editor.folds.is_hidden(15));
+    }
+
+    fn assert_cursor_visible(editor: &Editor, width: usize) {
+        let (column, scroll) = (editor.position.column, editor.scroll_offset.column);
+
+        assert!(
+            scroll <= column && column < scroll + width,
+            "column {} outside {}..{}",
+            column,
+            scroll,
+            scroll + width
+        );
+    }
+
+    #[test]
+    fn horizontal_scroll_follows_the_remembered_column() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let long = "x".repeat(200);
+
+        let mut editor = editor(&[&long, "abc", &long]);
+
+        editor.move_to_line_end(boundaries, false).unwrap();
+
+        let column = editor.position.column;
+
+        assert!(editor.scroll_offset.column > 0);
+        assert_cursor_visible(&editor, 80);
+
+        editor.move_down(boundaries, 1).unwrap();
+
+        assert_eq!(editor.position.column, 3);
+        assert_cursor_visible(&editor, 80);
+
+        editor.move_down(boundaries, 1).unwrap();
+
+        assert_eq!(editor.position.column, column);
+        assert_cursor_visible(&editor, 80);
+
+        editor.move_up(boundaries, 1).unwrap();
+        editor.move_up(boundaries, 1).unwrap();
+
+        assert_eq!(editor.position.column, column);
+        assert_cursor_visible(&editor, 80);
+    }
+
+    #[test]
+    fn moving_onto_shorter_rows_never_scrolls_the_text_away() {
+        let boundaries = Boundaries::new(80, 10);
+
+        let (long, medium) = ("x".repeat(200), "y".repeat(100));
+
+        let mut editor = editor(&["", &medium, &long, &medium, ""]);
+
+        editor.position.row = 2;
+
+        editor.move_to_line_end(boundaries, false).unwrap();
+
+        assert!(editor.scroll_offset.column > 100);
+
+        // Rows ending left of the window used to leave it where it was,
+        // showing nothing.
+        for _ in 0..2 {
+            editor.move_up(boundaries, 1).unwrap();
+            assert_cursor_visible(&editor, 80);
+        }
+
+        editor.move_down(boundaries, 2).unwrap();
+        assert_cursor_visible(&editor, 80);
+
+        for _ in 0..2 {
+            editor.move_down(boundaries, 1).unwrap();
+            assert_cursor_visible(&editor, 80);
+        }
+
+        editor.move_up(boundaries, 1).unwrap();
+        editor.move_left(boundaries, 50).unwrap();
+
+        assert_cursor_visible(&editor, 80);
+    }
+
+    #[test]
+    fn moving_on_one_and_two_row_terminals() {
+        for (height, wrap) in [(1, false), (2, false), (1, true), (2, true)] {
+            let boundaries = Boundaries::new(80, height);
+
+            let long = "x".repeat(300);
+
+            let mut editor = editor(&["a", &long, "b", "", &long, "c"]);
+
+            editor.config.wrap = wrap;
+
+            let check = |editor: &mut Editor| {
+                editor.scroll_into_view(boundaries);
+
+                // A wrapped row taller than the screen shows from its start.
+                assert!(
+                    editor.visual_cursor(boundaries).0 < height as usize
+                        || editor.scroll_offset.row == editor.position.row
+                );
+            };
+
+            for _ in 0..8 {
+                editor.move_down(boundaries, 1).unwrap();
+                check(&mut editor);
+            }
+
+            editor.move_to_line_end(boundaries, false).unwrap();
+            check(&mut editor);
+
+            editor.move_up(boundaries, 3).unwrap();
+            check(&mut editor);
+
+            editor.move_display_lines(boundaries, true, 5).unwrap();
+            check(&mut editor);
+
+            editor.move_display_lines(boundaries, false, 50).unwrap();
+            check(&mut editor);
+
+            editor.move_right(boundaries, 500).unwrap();
+            editor.move_left(boundaries, 2).unwrap();
+            check(&mut editor);
+
+            editor.goto_line(boundaries, 5);
+            check(&mut editor);
+
+            editor.move_up(boundaries, 100).unwrap();
+            check(&mut editor);
+
+            assert_eq!(editor.position.row, 0);
         }
     }
+
+    #[test]
+    fn config_toml_indentation_wins_over_detection() {
+        let rows = ["fn a() {", "    if b {", "        c();", "    }", "}"];
+
+        let mut editor = editor(&rows);
+
+        let config = editor.file_config();
+
+        assert_eq!((config.tab_width, config.expand_tabs), (4, true));
+
+        editor.user_config = Config::parse("tab_width = 2\nexpand_tabs = false\n");
+
+        let config = editor.file_config();
+
+        assert_eq!((config.tab_width, config.expand_tabs), (2, false));
+
+        editor.user_config = Config::parse("tab_width = 8\n");
+
+        let config = editor.file_config();
+
+        assert_eq!((config.tab_width, config.expand_tabs), (8, true));
+    }
+
+    #[test]
+    fn editorconfig_charset_decodes_the_file_again() {
+        let dir = std::env::temp_dir().join(format!("wind-{}-charset", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join(".editorconfig"),
+            "root = true\n[*]\ncharset = latin1\n",
+        )
+        .unwrap();
+
+        let path = dir.join("latin1.txt");
+
+        // Valid UTF-8 for "é", but "Ã©" in Latin-1.
+        fs::write(&path, b"\xc3\xa9\n").unwrap();
+
+        let mut editor = editor(&[""]);
+
+        assert!(editor.load_file(path.clone(), false));
+
+        assert_eq!(editor.document.rows[0].content, "Ã©");
+        assert_eq!(editor.document.encoding, Encoding::Latin1);
+
+        editor.save(false);
+
+        assert_eq!(fs::read(&path).unwrap(), b"\xc3\xa9\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_refused_save_leaves_the_buffer_untrimmed() {
+        let path = std::env::temp_dir().join(format!("wind-{}-refused-save", std::process::id()));
+
+        fs::write(&path, "text  \n").unwrap();
+
+        let mut editor = editor(&[""]);
+
+        assert!(editor.load_file(path.clone(), false));
+
+        editor.config.trim_on_save = true;
+
+        fs::write(&path, "changed elsewhere\n").unwrap();
+
+        editor.save(false);
+
+        assert_eq!(editor.document.rows[0].content, "text  ");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed elsewhere\n");
+
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,347 @@
+use crate::document::LineEnding;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+#[derive(Clone, Default)]
+pub struct EditorConfigSettings {
+    pub indent_style: Option<IndentStyle>,
+    pub indent_size: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<String>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+impl EditorConfigSettings {
+    fn apply(&mut self, key: &str, value: &str) {
+        let unset = value == "unset";
+
+        let boolean = || match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+
+        match key {
+            "indent_style" => {
+                self.indent_style = match value {
+                    "tab" => Some(IndentStyle::Tab),
+                    "space" => Some(IndentStyle::Space),
+                    _ => None,
+                }
+            }
+
+            // `tab` means "use tab_width", which is already the default.
+            "indent_size" if value == "tab" || unset => self.indent_size = None,
+
+            "indent_size" => {
+                if let Some(size) = value.parse().ok().filter(|size| *size > 0) {
+                    self.indent_size = Some(size);
+                }
+            }
+
+            "end_of_line" => {
+                self.end_of_line = match value {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::CrLf),
+                    "cr" => Some(LineEnding::Cr),
+                    _ => None,
+                }
+            }
+
+            "charset" if unset => self.charset = None,
+
+            "charset" => self.charset = Some(value.to_owned()),
+
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = boolean(),
+
+            "insert_final_newline" => self.insert_final_newline = boolean(),
+
+            _ => (),
+        }
+    }
+}
+
+struct Section {
+    glob: String,
+    properties: Vec<(String, String)>,
+}
+
+struct EditorConfigFile {
+    root: bool,
+    sections: Vec<Section>,
+}
+
+impl EditorConfigFile {
+    fn parse(text: &str) -> EditorConfigFile {
+        let mut file = EditorConfigFile {
+            root: false,
+            sections: Vec::new(),
+        };
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                file.sections.push(Section {
+                    glob: glob.to_owned(),
+                    properties: Vec::new(),
+                });
+
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            let key = key.trim().to_lowercase();
+
+            let value = value.trim().to_lowercase();
+
+            match file.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+
+                None if key == "root" => file.root = value == "true",
+
+                None => (),
+            }
+        }
+
+        file
+    }
+}
+
+pub struct EditorConfigParser;
+
+impl EditorConfigParser {
+    /// Resolves the settings for `path` from every `.editorconfig` between its
+    /// directory and the nearest one marked `root = true`. Closer files, and
+    /// later sections within a file, take precedence.
+    pub fn find_and_parse(path: &Path) -> EditorConfigSettings {
+        let mut settings = EditorConfigSettings::default();
+
+        let Some(path) = absolute(path) else {
+            return settings;
+        };
+
+        let mut files = Vec::new();
+
+        for dir in path.ancestors().skip(1) {
+            let Ok(text) = fs::read_to_string(dir.join(".editorconfig")) else {
+                continue;
+            };
+
+            let file = EditorConfigFile::parse(&text);
+
+            let root = file.root;
+
+            files.push((dir, file));
+
+            if root {
+                break;
+            }
+        }
+
+        for (dir, file) in files.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            for section in &file.sections {
+                if !section_matches(&section.glob, &relative) {
+                    continue;
+                }
+
+                for (key, value) in &section.properties {
+                    settings.apply(key, value);
+                }
+            }
+        }
+
+        settings
+    }
+}
+
+fn absolute(path: &Path) -> Option<PathBuf> {
+    if let Ok(path) = fs::canonicalize(path) {
+        return Some(path);
+    }
+
+    // The file may not exist yet, but its directory usually does.
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::canonicalize(parent).ok()?,
+        _ => env::current_dir().ok()?,
+    };
+
+    Some(parent.join(path.file_name()?))
+}
+
+/// A glob without a `/` matches the file name at any depth; otherwise it is
+/// anchored to the directory of the `.editorconfig` file.
+fn section_matches(glob: &str, relative: &str) -> bool {
+    let glob = if glob.contains('/') {
+        glob.strip_prefix('/').unwrap_or(glob).to_owned()
+    } else {
+        format!("**/{}", glob)
+    };
+
+    let glob = glob.chars().collect::<Vec<_>>();
+
+    let text = relative.chars().collect::<Vec<_>>();
+
+    // `**/` also matches no directories at all.
+    glob_match(&glob, &text)
+        || (glob.starts_with(&['*', '*', '/']) && glob_match(&glob[3..], &text))
+}
+
+/// Supports `*`, `**`, `?`, `[abc]`, `[!abc]`, `{a,b}` and `{1..5}`.
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    let Some((&first, rest)) = glob.split_first() else {
+        return text.is_empty();
+    };
+
+    match first {
+        '*' if rest.first() == Some(&'*') => {
+            (0..=text.len()).any(|skip| glob_match(&rest[1..], &text[skip..]))
+        }
+
+        '*' => (0..=text.len())
+            .take_while(|&skip| skip == 0 || text[skip - 1] != '/')
+            .any(|skip| glob_match(rest, &text[skip..])),
+
+        '?' => text.first().is_some_and(|&ch| ch != '/') && glob_match(rest, &text[1..]),
+
+        '[' => match rest.iter().position(|&ch| ch == ']') {
+            Some(end) => {
+                let (class, rest) = (&rest[..end], &rest[end + 1..]);
+
+                let (negated, class) = match class.split_first() {
+                    Some(('!', class)) => (true, class),
+                    _ => (false, class),
+                };
+
+                text.first()
+                    .is_some_and(|&ch| ch != '/' && class_contains(class, ch) != negated)
+                    && glob_match(rest, &text[1..])
+            }
+
+            None => text.first() == Some(&'[') && glob_match(rest, &text[1..]),
+        },
+
+        '{' => match closing_brace(rest) {
+            Some(end) => {
+                let (inner, rest) = (&rest[..end], &rest[end + 1..]);
+
+                if let Some((low, high)) = numeric_range(inner) {
+                    return (1..=text.len()).any(|len| {
+                        let number = text[..len].iter().collect::<String>();
+
+                        number
+                            .parse::<i64>()
+                            .is_ok_and(|n| (low..=high).contains(&n))
+                            && glob_match(rest, &text[len..])
+                    });
+                }
+
+                split_alternatives(inner).into_iter().any(|alternative| {
+                    let glob = [alternative, rest].concat();
+
+                    glob_match(&glob, text)
+                })
+            }
+
+            None => text.first() == Some(&'{') && glob_match(rest, &text[1..]),
+        },
+
+        '\\' if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob_match(&rest[1..], &text[1..])
+        }
+
+        ch => text.first() == Some(&ch) && glob_match(rest, &text[1..]),
+    }
+}
+
+fn class_contains(class: &[char], ch: char) -> bool {
+    let mut i = 0;
+
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if (class[i]..=class[i + 2]).contains(&ch) {
+                return true;
+            }
+
+            i += 3;
+        } else {
+            if class[i] == ch {
+                return true;
+            }
+
+            i += 1;
+        }
+    }
+
+    false
+}
+
+fn closing_brace(glob: &[char]) -> Option<usize> {
+    let mut depth = 0;
+
+    for (i, &ch) in glob.iter().enumerate() {
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    None
+}
+
+fn split_alternatives(inner: &[char]) -> Vec<&[char]> {
+    let mut alternatives = Vec::new();
+
+    let (mut depth, mut start) = (0, 0);
+
+    for (i, &ch) in inner.iter().enumerate() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&inner[start..i]);
+
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+
+    alternatives.push(&inner[start..]);
+
+    alternatives
+}
+
+fn numeric_range(inner: &[char]) -> Option<(i64, i64)> {
+    let inner = inner.iter().collect::<String>();
+
+    let (low, high) = inner.split_once("..")?;
+
+    Some((low.parse().ok()?, high.parse().ok()?))
+}
@@ -0,0 +1,222 @@
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub enum FileType {
+    Rust,
+    Python,
+    Go,
+    C,
+    Cpp,
+    Java,
+    JavaScript,
+    TypeScript,
+    Kotlin,
+    Swift,
+    Ruby,
+    Perl,
+    Shell,
+    Lua,
+    Sql,
+    Haskell,
+    Toml,
+    Yaml,
+    Json,
+    Markdown,
+    #[default]
+    Text,
+}
+
+impl FileType {
+    pub fn from_extension(extension: &str) -> Option<FileType> {
+        let filetype = match extension {
+            "rs" => FileType::Rust,
+            "py" | "pyw" => FileType::Python,
+            "go" => FileType::Go,
+            "c" | "h" => FileType::C,
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" => FileType::Cpp,
+            "java" => FileType::Java,
+            "js" | "mjs" | "cjs" => FileType::JavaScript,
+            "ts" => FileType::TypeScript,
+            "kt" | "kts" => FileType::Kotlin,
+            "swift" => FileType::Swift,
+            "rb" => FileType::Ruby,
+            "pl" | "pm" => FileType::Perl,
+            "sh" | "bash" | "zsh" => FileType::Shell,
+            "lua" => FileType::Lua,
+            "sql" => FileType::Sql,
+            "hs" => FileType::Haskell,
+            "toml" => FileType::Toml,
+            "yaml" | "yml" => FileType::Yaml,
+            "json" => FileType::Json,
+            "md" | "markdown" => FileType::Markdown,
+            "txt" => FileType::Text,
+            _ => return None,
+        };
+
+        Some(filetype)
+    }
+
+    pub fn from_interpreter(interpreter: &str) -> Option<FileType> {
+        let filetype = match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        {
+            "python" => FileType::Python,
+            "sh" | "bash" | "zsh" | "dash" | "ksh" => FileType::Shell,
+            "ruby" => FileType::Ruby,
+            "perl" => FileType::Perl,
+            "node" | "nodejs" | "deno" => FileType::JavaScript,
+            "lua" => FileType::Lua,
+            _ => return None,
+        };
+
+        Some(filetype)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileType::Rust => "rust",
+            FileType::Python => "python",
+            FileType::Go => "go",
+            FileType::C => "c",
+            FileType::Cpp => "cpp",
+            FileType::Java => "java",
+            FileType::JavaScript => "javascript",
+            FileType::TypeScript => "typescript",
+            FileType::Kotlin => "kotlin",
+            FileType::Swift => "swift",
+            FileType::Ruby => "ruby",
+            FileType::Perl => "perl",
+            FileType::Shell => "shellscript",
+            FileType::Lua => "lua",
+            FileType::Sql => "sql",
+            FileType::Haskell => "haskell",
+            FileType::Toml => "toml",
+            FileType::Yaml => "yaml",
+            FileType::Json => "json",
+            FileType::Markdown => "markdown",
+            FileType::Text => "text",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FileType> {
+        FileType::ALL
+            .into_iter()
+            .find(|filetype| filetype.name() == name)
+    }
+
+    pub fn comment_string(&self) -> Option<&'static str> {
+        match self {
+            FileType::Rust
+            | FileType::Go
+            | FileType::C
+            | FileType::Cpp
+            | FileType::Java
+            | FileType::JavaScript
+            | FileType::TypeScript
+            | FileType::Kotlin
+            | FileType::Swift => Some("//"),
+            FileType::Python
+            | FileType::Ruby
+            | FileType::Perl
+            | FileType::Shell
+            | FileType::Toml
+            | FileType::Yaml => Some("#"),
+            FileType::Lua | FileType::Sql | FileType::Haskell => Some("--"),
+            FileType::Json | FileType::Markdown | FileType::Text => None,
+        }
+    }
+
+    pub fn tab_width(&self) -> usize {
+        match self {
+            FileType::Go => 8,
+            FileType::JavaScript
+            | FileType::TypeScript
+            | FileType::Ruby
+            | FileType::Lua
+            | FileType::Haskell
+            | FileType::Yaml
+            | FileType::Json
+            | FileType::Markdown => 2,
+            _ => 4,
+        }
+    }
+
+    pub fn expand_tabs(&self) -> bool {
+        *self != FileType::Go
+    }
+
+    /// The command `format_on_save` pipes the document through, reading it
+    /// on stdin and writing the result to stdout. `{file}` stands for the
+    /// file's path.
+    pub fn formatter(&self) -> Option<&'static str> {
+        match self {
+            FileType::Rust => Some("rustfmt --emit stdout"),
+            FileType::Python => Some("black --quiet -"),
+            FileType::Go => Some("gofmt"),
+            FileType::C | FileType::Cpp => Some("clang-format --assume-filename={file}"),
+            FileType::JavaScript | FileType::TypeScript | FileType::Json | FileType::Markdown => {
+                Some("prettier --stdin-filepath {file}")
+            }
+            _ => None,
+        }
+    }
+
+    pub const ALL: [FileType; 21] = [
+        FileType::Rust,
+        FileType::Python,
+        FileType::Go,
+        FileType::C,
+        FileType::Cpp,
+        FileType::Java,
+        FileType::JavaScript,
+        FileType::TypeScript,
+        FileType::Kotlin,
+        FileType::Swift,
+        FileType::Ruby,
+        FileType::Perl,
+        FileType::Shell,
+        FileType::Lua,
+        FileType::Sql,
+        FileType::Haskell,
+        FileType::Toml,
+        FileType::Yaml,
+        FileType::Json,
+        FileType::Markdown,
+        FileType::Text,
+    ];
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub fn detect_file_type(path: &Path, first_line: &str) -> FileType {
+    let by_extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(FileType::from_extension);
+
+    if let Some(filetype) = by_extension {
+        return filetype;
+    }
+
+    let Some(shebang) = first_line.strip_prefix("#!") else {
+        return FileType::Text;
+    };
+
+    let mut words = shebang.split_whitespace();
+
+    let mut interpreter = words.next().unwrap_or_default();
+
+    if interpreter.ends_with("/env") {
+        interpreter = words
+            .find(|word| !word.starts_with('-'))
+            .unwrap_or_default();
+    }
+
+    let name = interpreter.rsplit('/').next().unwrap_or_default();
+
+    FileType::from_interpreter(name).unwrap_or_default()
+}
@@ -0,0 +1,213 @@
+#[derive(Clone, Copy, PartialEq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub open: bool,
+}
+
+/// Manually created folds, sorted by `start` and never overlapping.
+#[derive(Default)]
+pub struct Folds {
+    folds: Vec<Fold>,
+    rows: usize,
+}
+
+impl Folds {
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    pub fn at(&self, row: usize) -> Option<&Fold> {
+        let index = self.folds.partition_point(|fold| fold.start <= row);
+
+        self.folds[..index].last().filter(|fold| row <= fold.end)
+    }
+
+    pub fn closed_at(&self, row: usize) -> Option<&Fold> {
+        self.at(row).filter(|fold| !fold.open)
+    }
+
+    pub fn is_hidden(&self, row: usize) -> bool {
+        self.closed_at(row).is_some_and(|fold| row > fold.start)
+    }
+
+    pub fn visible(&self, row: usize) -> usize {
+        self.closed_at(row).map_or(row, |fold| fold.start)
+    }
+
+    pub fn add(&mut self, start: usize, end: usize) {
+        self.folds
+            .retain(|fold| fold.end < start || fold.start > end);
+
+        let index = self.folds.partition_point(|fold| fold.start < start);
+
+        self.folds.insert(
+            index,
+            Fold {
+                start,
+                end,
+                open: false,
+            },
+        );
+    }
+
+    pub fn toggle(&mut self, row: usize) -> bool {
+        let index = self.folds.partition_point(|fold| fold.start <= row);
+
+        match index
+            .checked_sub(1)
+            .and_then(|index| self.folds.get_mut(index))
+            .filter(|fold| row <= fold.end)
+        {
+            Some(fold) => {
+                fold.open = !fold.open;
+
+                true
+            }
+
+            None => false,
+        }
+    }
+
+    pub fn open_at(&mut self, row: usize) {
+        if self.closed_at(row).is_some() {
+            self.toggle(row);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.folds.clear();
+    }
+
+    /// Follows an edit at `row` that left the document with `rows` rows:
+    /// later folds move with the rows below, and a fold holding `row`
+    /// grows or shrinks.
+    pub fn adjust(&mut self, row: usize, rows: usize) {
+        let delta = rows as isize - self.rows as isize;
+
+        self.rows = rows;
+
+        if delta == 0 {
+            return;
+        }
+
+        let shift = |index: usize| index.saturating_add_signed(delta);
+
+        for fold in &mut self.folds {
+            if fold.start > row {
+                fold.start = shift(fold.start);
+
+                fold.end = shift(fold.end);
+            } else if fold.end >= row {
+                fold.end = shift(fold.end).max(fold.start);
+            }
+        }
+
+        self.folds
+            .retain(|fold| fold.start < fold.end && fold.end < rows);
+    }
+
+    pub fn visible_rows(&self, from: usize, to: usize) -> usize {
+        let hidden: usize = self
+            .folds
+            .iter()
+            .filter(|fold| !fold.open)
+            .map(|fold| {
+                let first = (fold.start + 1).max(from);
+
+                let last = fold.end.min(to.saturating_sub(1));
+
+                (last + 1).saturating_sub(first)
+            })
+            .sum();
+
+        to.saturating_sub(from).saturating_sub(hidden)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folds(rows: usize, spans: &[(usize, usize)]) -> Folds {
+        let mut folds = Folds::default();
+
+        folds.adjust(0, rows);
+
+        for &(start, end) in spans {
+            folds.add(start, end);
+        }
+
+        folds
+    }
+
+    fn spans(folds: &Folds) -> Vec<(usize, usize, bool)> {
+        folds
+            .folds
+            .iter()
+            .map(|fold| (fold.start, fold.end, fold.open))
+            .collect()
+    }
+
+    #[test]
+    fn added_folds_replace_the_ones_they_overlap() {
+        let mut folds = folds(20, &[(10, 12), (2, 4), (6, 8)]);
+
+        assert_eq!(
+            spans(&folds),
+            [(2, 4, false), (6, 8, false), (10, 12, false)]
+        );
+
+        folds.add(3, 7);
+
+        assert_eq!(spans(&folds), [(3, 7, false), (10, 12, false)]);
+
+        assert!(folds.is_hidden(5));
+        assert_eq!(folds.visible(5), 3);
+        assert!(!folds.is_hidden(3));
+        assert!(!folds.is_hidden(9));
+    }
+
+    #[test]
+    fn toggle_opens_and_closes_the_fold_holding_the_row() {
+        let mut folds = folds(20, &[(2, 4)]);
+
+        assert!(!folds.toggle(5));
+        assert!(!folds.toggle(1));
+
+        assert!(folds.toggle(4));
+        assert_eq!(spans(&folds), [(2, 4, true)]);
+        assert!(!folds.is_hidden(3));
+
+        assert!(folds.toggle(2));
+        assert_eq!(spans(&folds), [(2, 4, false)]);
+
+        folds.open_at(3);
+        folds.open_at(3);
+        assert_eq!(spans(&folds), [(2, 4, true)]);
+    }
+
+    #[test]
+    fn adjust_moves_and_resizes_folds_with_their_rows() {
+        let mut folds = folds(10, &[(2, 5)]);
+
+        // Two rows added above the fold move it down.
+        folds.adjust(0, 12);
+        assert_eq!(spans(&folds), [(4, 7, false)]);
+
+        // Two rows added inside it make it longer.
+        folds.adjust(5, 14);
+        assert_eq!(spans(&folds), [(4, 9, false)]);
+
+        // Rows added below it leave it alone.
+        folds.adjust(12, 16);
+        assert_eq!(spans(&folds), [(4, 9, false)]);
+
+        // Rows deleted inside it make it shorter, down to nothing.
+        folds.adjust(4, 12);
+        assert_eq!(spans(&folds), [(4, 5, false)]);
+
+        folds.adjust(4, 11);
+        assert!(folds.is_empty());
+    }
+}
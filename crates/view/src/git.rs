@@ -0,0 +1,86 @@
+use anyhow::{bail, Result};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+#[derive(Default)]
+pub struct GitDiff {
+    pub lines: HashMap<usize, DiffKind>,
+}
+
+impl GitDiff {
+    pub fn compute(path: &Path) -> Result<GitDiff> {
+        let file_name = path.file_name().unwrap_or_default();
+
+        let mut command = Command::new("git");
+
+        command.args(["diff", "--no-color", "--no-ext-diff", "--unified=0", "--"]);
+        command.arg(file_name);
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            command.current_dir(parent);
+        }
+
+        let output = command.output()?;
+
+        if !output.status.success() {
+            bail!("git diff failed for {}", path.display());
+        }
+
+        Ok(GitDiff::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    pub fn parse(diff: &str) -> GitDiff {
+        let mut lines = HashMap::new();
+
+        for header in diff.lines().filter(|line| line.starts_with("@@ ")) {
+            let mut ranges = header.split_whitespace().skip(1);
+
+            let (Some(old), Some(new)) = (ranges.next(), ranges.next()) else {
+                continue;
+            };
+
+            let (_, old_count) = parse_range(old.trim_start_matches('-'));
+            let (new_start, new_count) = parse_range(new.trim_start_matches('+'));
+
+            if new_count == 0 {
+                lines.insert(new_start.saturating_sub(1), DiffKind::Removed);
+
+                continue;
+            }
+
+            let kind = if old_count == 0 {
+                DiffKind::Added
+            } else {
+                DiffKind::Modified
+            };
+
+            let start = new_start.saturating_sub(1);
+
+            for row in start..start + new_count {
+                lines.insert(row, kind);
+            }
+        }
+
+        GitDiff { lines }
+    }
+
+    #[inline]
+    pub fn get(&self, row: usize) -> Option<DiffKind> {
+        self.lines.get(&row).copied()
+    }
+}
+
+fn parse_range(range: &str) -> (usize, usize) {
+    let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+
+    (start.parse().unwrap_or(0), count.parse().unwrap_or(0))
+}
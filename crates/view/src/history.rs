@@ -0,0 +1,77 @@
+use crate::document::Row;
+
+pub struct Change {
+    pub row: usize,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl Change {
+    fn apply(rows: &mut Vec<Row>, row: usize, from: &[String], to: &[String]) {
+        rows.splice(row..row + from.len(), to.iter().cloned().map(Row::from));
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Vec<Change>>,
+    redo_stack: Vec<Vec<Change>>,
+    group: Option<Vec<Change>>,
+}
+
+impl History {
+    pub fn record(&mut self, change: Change) {
+        self.redo_stack.clear();
+
+        match self.group.as_mut() {
+            Some(group) => group.push(change),
+            None => self.undo_stack.push(vec![change]),
+        }
+    }
+
+    pub fn begin_group(&mut self) {
+        self.end_group();
+
+        self.group = Some(Vec::new());
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.group.take() {
+            if !group.is_empty() {
+                self.undo_stack.push(group);
+            }
+        }
+    }
+
+    pub fn undo(&mut self, rows: &mut Vec<Row>) -> Option<usize> {
+        self.end_group();
+
+        let changes = self.undo_stack.pop()?;
+
+        for change in changes.iter().rev() {
+            Change::apply(rows, change.row, &change.after, &change.before);
+        }
+
+        let row = changes.iter().map(|c| c.row).min();
+
+        self.redo_stack.push(changes);
+
+        row
+    }
+
+    pub fn redo(&mut self, rows: &mut Vec<Row>) -> Option<usize> {
+        self.end_group();
+
+        let changes = self.redo_stack.pop()?;
+
+        for change in changes.iter() {
+            Change::apply(rows, change.row, &change.before, &change.after);
+        }
+
+        let row = changes.iter().map(|c| c.row).min();
+
+        self.undo_stack.push(changes);
+
+        row
+    }
+}
@@ -1,4 +1,18 @@
 pub mod boundaries;
+pub mod buffers;
+pub mod config;
+pub mod diagnostics;
 pub mod document;
 pub mod editor;
-mod position;
+pub mod editorconfig;
+pub mod filetype;
+pub mod fold;
+pub mod git;
+pub mod history;
+pub mod messages;
+pub mod modeline;
+pub mod position;
+pub mod positions;
+pub mod quickfix;
+pub mod recent;
+pub mod recovery;
@@ -0,0 +1,40 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MAX_MESSAGES: usize = 100;
+
+pub struct MessageLog {
+    start: Instant,
+    entries: VecDeque<(Duration, String)>,
+}
+
+impl Default for MessageLog {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl MessageLog {
+    pub fn push(&mut self, message: String) {
+        if self.entries.len() == MAX_MESSAGES {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back((self.start.elapsed(), message));
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|(elapsed, message)| format!("[{:>8.1}s] {}", elapsed.as_secs_f64(), message))
+            .collect()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,69 @@
+use crate::filetype::FileType;
+
+#[derive(Clone, Default)]
+pub struct Modeline {
+    pub tab_width: Option<usize>,
+    pub expand_tabs: Option<bool>,
+    pub filetype: Option<FileType>,
+}
+
+impl Modeline {
+    pub fn parse(line: &str) -> Option<Modeline> {
+        let options = ["vim:", "vi:", "ex:"].into_iter().find_map(|marker| {
+            let index = line.find(marker)?;
+
+            let at_word_start = line[..index]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace);
+
+            at_word_start.then(|| &line[index + marker.len()..])
+        })?;
+
+        let options = options.trim_start();
+
+        // The `set` form ends at the next `:` and only separates with spaces.
+        let options: Vec<&str> = match options
+            .strip_prefix("set ")
+            .or_else(|| options.strip_prefix("se "))
+        {
+            Some(options) => options
+                .split(':')
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect(),
+            None => options
+                .split(|c: char| c == ':' || c.is_whitespace())
+                .filter(|option| !option.is_empty())
+                .collect(),
+        };
+
+        let mut modeline = Modeline::default();
+
+        for option in options {
+            match option.split_once('=') {
+                Some(("ts" | "tabstop" | "sw" | "shiftwidth", value)) => {
+                    if let Some(width) = value.parse().ok().filter(|width| *width > 0) {
+                        modeline.tab_width = Some(width);
+                    }
+                }
+
+                Some(("ft" | "filetype", name)) => {
+                    modeline.filetype =
+                        FileType::from_name(name).or_else(|| FileType::from_interpreter(name));
+                }
+
+                Some(_) => (),
+
+                None => match option {
+                    "et" | "expandtab" => modeline.expand_tabs = Some(true),
+                    "noet" | "noexpandtab" => modeline.expand_tabs = Some(false),
+                    _ => (),
+                },
+            }
+        }
+
+        Some(modeline)
+    }
+}
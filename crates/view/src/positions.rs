@@ -0,0 +1,68 @@
+use crate::config::Config;
+use crate::position::Position;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const POSITIONS_LEN: usize = 1000;
+
+/// Where the last cursor position of each file is kept, one
+/// `row<TAB>column<TAB>path` line per file, most recent last.
+pub fn positions_path() -> Option<PathBuf> {
+    Some(Config::data_dir()?.join("positions"))
+}
+
+fn read_positions() -> Vec<(PathBuf, usize, usize)> {
+    let Some(text) = positions_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+
+            let row = fields.next()?.parse().ok()?;
+
+            let column = fields.next()?.parse().ok()?;
+
+            Some((PathBuf::from(fields.next()?), row, column))
+        })
+        .collect()
+}
+
+pub fn load_position(path: &Path) -> Option<Position> {
+    read_positions()
+        .into_iter()
+        .rfind(|(file, ..)| file == path)
+        .map(|(_, row, column)| Position {
+            row,
+            column,
+            ..Default::default()
+        })
+}
+
+pub fn save_position(path: &Path, position: Position) -> io::Result<()> {
+    let Some(positions_path) = positions_path() else {
+        return Ok(());
+    };
+
+    let mut positions = read_positions();
+
+    positions.retain(|(file, ..)| file != path);
+
+    positions.push((path.to_path_buf(), position.row, position.column));
+
+    let excess = positions.len().saturating_sub(POSITIONS_LEN);
+
+    let text: String = positions[excess..]
+        .iter()
+        .map(|(file, row, column)| format!("{}\t{}\t{}\n", row, column, file.display()))
+        .collect();
+
+    if let Some(dir) = positions_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(positions_path, text)
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct QuickfixEntry {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct QuickfixList {
+    pub entries: Vec<QuickfixEntry>,
+    pub current: Option<usize>,
+}
+
+impl QuickfixList {
+    pub fn new(entries: Vec<QuickfixEntry>) -> QuickfixList {
+        QuickfixList {
+            entries,
+            current: None,
+        }
+    }
+
+    pub fn select_next(&mut self) -> Option<&QuickfixEntry> {
+        let index = self.current.map_or(0, |current| current + 1);
+
+        self.select(index)
+    }
+
+    pub fn select_previous(&mut self) -> Option<&QuickfixEntry> {
+        let index = self.current?.checked_sub(1)?;
+
+        self.select(index)
+    }
+
+    fn select(&mut self, index: usize) -> Option<&QuickfixEntry> {
+        let entry = self.entries.get(index)?;
+
+        self.current = Some(index);
+
+        Some(entry)
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}:{}:{}: {}",
+                    entry.path.display(),
+                    entry.line + 1,
+                    entry.col + 1,
+                    entry.text.trim()
+                )
+            })
+            .collect()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
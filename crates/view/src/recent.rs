@@ -0,0 +1,74 @@
+use crate::config::Config;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const RECENT_LEN: usize = 100;
+
+pub fn recent_path() -> Option<PathBuf> {
+    Some(Config::data_dir()?.join("recent"))
+}
+
+pub fn load_recent() -> Vec<PathBuf> {
+    recent_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|text| {
+            text.lines()
+                .map(PathBuf::from)
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn record_recent(path: &Path) -> io::Result<()> {
+    let Some(recent_path) = recent_path() else {
+        return Ok(());
+    };
+
+    let mut files = load_recent();
+
+    files.retain(|file| file != path);
+
+    files.insert(0, path.to_path_buf());
+
+    files.truncate(RECENT_LEN);
+
+    let text: String = files
+        .iter()
+        .map(|file| format!("{}\n", file.display()))
+        .collect();
+
+    if let Some(dir) = recent_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(recent_path, text)
+}
+
+pub struct RecentFiles {
+    pub files: Vec<PathBuf>,
+    pub selected: usize,
+}
+
+impl RecentFiles {
+    pub fn new(files: Vec<PathBuf>) -> RecentFiles {
+        RecentFiles { files, selected: 0 }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        if self.files.is_empty() {
+            return;
+        }
+
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.files.len() - 1);
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.files.get(self.selected).map(PathBuf::as_path)
+    }
+}
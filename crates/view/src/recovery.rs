@@ -0,0 +1,114 @@
+use crate::config::Config;
+use crate::document::Document;
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn recovery_dir() -> Option<PathBuf> {
+    Some(Config::data_dir()?.join("recovery"))
+}
+
+/// Writes `document` to `<prefix><timestamp>` in the recovery directory.
+/// Runs while panicking, so it only uses plain `fs` calls.
+pub fn write_recovery(document: &Document) -> io::Result<PathBuf> {
+    let dir = recovery_dir().ok_or_else(|| io::Error::other("no home directory"))?;
+
+    fs::create_dir_all(&dir)?;
+
+    let prefix = document
+        .canonical_path()
+        .and_then(recovery_prefix)
+        .unwrap_or_else(|| "untitled_".to_owned());
+
+    let path = dir.join(format!("{}{}", prefix, unix_time(SystemTime::now())));
+
+    fs::write(&path, document.text())?;
+
+    Ok(path)
+}
+
+/// The newest recovery file for `document` that still differs from it.
+/// Files that are older than the file on disk, or hold the same text, are
+/// stale and removed.
+pub fn find_recovery(document: &Document) -> Option<PathBuf> {
+    let path = document.canonical_path()?;
+
+    let prefix = recovery_prefix(path)?;
+
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(unix_time)
+        .unwrap_or(0);
+
+    let text_hash = hash(&document.text());
+
+    let mut newest: Option<(u64, PathBuf)> = None;
+
+    for entry in fs::read_dir(recovery_dir()?).ok()?.flatten() {
+        let file_name = entry.file_name();
+
+        let Some(timestamp) = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        let recovery_path = entry.path();
+
+        let stale = timestamp < modified
+            || fs::read_to_string(&recovery_path).map_or(true, |text| hash(&text) == text_hash);
+
+        if stale {
+            let _ = fs::remove_file(&recovery_path);
+        } else if newest
+            .as_ref()
+            .is_none_or(|(newest, _)| timestamp > *newest)
+        {
+            newest = Some((timestamp, recovery_path));
+        }
+    }
+
+    newest.map(|(_, path)| path)
+}
+
+fn recovery_prefix(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy();
+
+    Some(format!("{}_{:016x}_", name, hash(&path.to_string_lossy())))
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    text.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_of_the_same_name_get_their_own_prefix() {
+        let a = recovery_prefix(Path::new("/a/main.rs")).unwrap();
+
+        let b = recovery_prefix(Path::new("/b/main.rs")).unwrap();
+
+        assert!(a.starts_with("main.rs_") && b.starts_with("main.rs_"));
+        assert_ne!(a, b);
+        assert_eq!(a, recovery_prefix(Path::new("/a/main.rs")).unwrap());
+    }
+}